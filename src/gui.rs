@@ -0,0 +1,1001 @@
+//! The interactive view of the instantiation graph.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::mpsc::Receiver;
+use std::time::Duration;
+
+use eframe::{run_native, App, CreationContext};
+use egui::{
+    epaint::TextShape, Color32, Context, FontFamily, FontId, Rect, Rounding, Shape, Stroke, Vec2,
+};
+use egui_graphs::{Edge, Graph, GraphView, Metadata, SettingsInteraction, SettingsStyle};
+use egui_plot::{Bar, BarChart, Plot};
+use petgraph::{stable_graph::NodeIndex, Directed};
+
+use gordian_profiler::{
+    generate_graph, heat, heat_rgb, percentage, truncate_name, InstantiationGraph, Profiler,
+};
+
+/// A displayed node. The custom draw function cannot capture anything, so
+/// all it needs to know is carried here, along with the stats for the hover
+/// tooltip.
+#[derive(Debug, Clone)]
+struct GuiNode {
+    key: (u64, usize),
+    name: String,
+    /// Instantiations of the node's quantifier on a log scale, from 0 for
+    /// none to 1 for the most instantiated quantifier
+    heat: f32,
+    instantiations: u64,
+    cost: u64,
+    /// Share of all instantiations, in percent
+    share: f64,
+    /// Degrees in the whole graph, not just the displayed part
+    in_degree: usize,
+    out_degree: usize,
+    /// Drawn faintly because it does not match the search
+    dimmed: bool,
+}
+
+/// See `heat_rgb`.
+fn heat_color(heat: f32) -> Color32 {
+    let [r, g, b] = heat_rgb(heat);
+    Color32::from_rgb(r, g, b)
+}
+
+/// The part of `graph` to display, colored by the stats in `profiler`, with
+/// heavier edges drawn thicker.
+fn display_graph(profiler: &Profiler, graph: &InstantiationGraph) -> Graph<GuiNode, u64, Directed> {
+    let costs: HashMap<&str, (u64, u64)> = profiler
+        .quantifier_stats
+        .iter()
+        .map(|cost| (cost.quant.as_str(), (cost.instantiations, cost.cost)))
+        .collect();
+    let max = costs.values().map(|c| c.0).max().unwrap_or(0);
+    let total = profiler.total_instantiations();
+    let full = &profiler.instantiation_graph;
+    let mut in_degrees: HashMap<(u64, usize), usize> = HashMap::new();
+    for tgt in full.edges.values().flatten() {
+        *in_degrees.entry(*tgt).or_default() += 1;
+    }
+    let g = generate_graph(graph).map(
+        |_, (key, name)| {
+            let (instantiations, cost) = costs.get(name.as_str()).copied().unwrap_or_default();
+            GuiNode {
+                key: *key,
+                name: name.clone(),
+                heat: heat(instantiations, max),
+                instantiations,
+                cost,
+                share: percentage(instantiations, total),
+                in_degree: in_degrees.get(key).copied().unwrap_or(0),
+                out_degree: full.edges.get(key).map_or(0, HashSet::len),
+                dimmed: false,
+            }
+        },
+        |_, e| *e,
+    );
+    let mut g = Graph::from(&g);
+    let weights: Vec<_> = g
+        .edges_iter()
+        .map(|(idx, e)| (idx, e.data().copied().unwrap_or(1)))
+        .collect();
+    for (idx, weight) in weights {
+        if let Some(edge) = g.edge_mut(idx) {
+            *edge = edge.clone().with_width(1. + (weight as f32).log2());
+        }
+    }
+    g
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum NodeAction {
+    FocusNeighborhood,
+    CopyName,
+    Hide,
+    OnlyDescendants,
+    Explain,
+}
+
+impl NodeAction {
+    const ALL: [NodeAction; 5] = [
+        NodeAction::FocusNeighborhood,
+        NodeAction::CopyName,
+        NodeAction::Hide,
+        NodeAction::OnlyDescendants,
+        NodeAction::Explain,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            NodeAction::FocusNeighborhood => "Focus neighborhood",
+            NodeAction::CopyName => "Copy full name",
+            NodeAction::Hide => "Hide",
+            NodeAction::OnlyDescendants => "Show only descendants",
+            NodeAction::Explain => "Explain",
+        }
+    }
+}
+
+/// How the displayed nodes are placed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum GraphLayout {
+    /// egui_graphs' own placement, scattered by `layout_seed` if given
+    Default,
+    /// One row per `InstantiationGraph::depths` level, roots at the top, as
+    /// the graph is mostly acyclic and the trigger relation goes one way
+    Layered,
+}
+
+impl GraphLayout {
+    const ALL: [GraphLayout; 2] = [GraphLayout::Default, GraphLayout::Layered];
+
+    fn label(self) -> &'static str {
+        match self {
+            GraphLayout::Default => "Default",
+            GraphLayout::Layered => "Layered by depth",
+        }
+    }
+}
+
+pub struct BasicApp {
+    g: Graph<GuiNode, u64, Directed>,
+    profiler: Profiler,
+    /// Instantiations of the most instantiated quantifier, for the legend
+    max_instantiations: u64,
+    /// Nodes to display, or `None` for the whole graph; `hidden` applies on top
+    visible: Option<HashSet<(u64, usize)>>,
+    hidden: HashSet<(u64, usize)>,
+    /// Edges lighter than this are not displayed, see
+    /// `BasicApp::apply_min_weight`
+    min_weight: u64,
+    /// Edges `apply_min_weight` took out of `g`, to put back once the
+    /// threshold is lowered again
+    light_edges: Vec<(NodeIndex, NodeIndex, Edge<u64>)>,
+    /// Also drop the nodes that only light edges connected, which rebuilds
+    /// the graph, see `InstantiationGraph::without_edges_below`
+    drop_isolated: bool,
+    /// Whether clicking a node shows or hides its successors, see
+    /// `BasicApp::explored`
+    explore: bool,
+    /// Nodes whose successors are shown while exploring
+    expanded: HashSet<(u64, usize)>,
+    /// Node that was under the pointer when the context menu was opened
+    menu_node: Option<(u64, usize)>,
+    explained: Option<(u64, usize)>,
+    /// Node last stepped to with `n` or `p`, see `BasicApp::ranked_nodes`
+    stepped: Option<(u64, usize)>,
+    layout_seed: Option<u64>,
+    layout: GraphLayout,
+    show_histogram: bool,
+    /// Canvas location to pan the graph view to on the next frame
+    center_on: Option<Vec2>,
+    /// Zoom and pan to fit all displayed nodes on the next frame
+    fit_view: bool,
+    search: String,
+    /// Where "Save PNG" writes to
+    png_path: String,
+    /// Part of the window to save once the screenshot has been rendered, in
+    /// points, with the points per pixel at the time
+    pending_png: Option<(Rect, f32)>,
+    /// Outcome of the last "Save PNG"
+    png_status: Option<String>,
+    /// Profiles of the changed trace with `--watch`, to replace `profiler`
+    updates: Option<Receiver<Profiler>>,
+}
+
+impl BasicApp {
+    fn new(
+        _: &CreationContext<'_>,
+        profiler: Profiler,
+        layout_seed: Option<u64>,
+        collapsed: bool,
+        updates: Option<Receiver<Profiler>>,
+    ) -> Self {
+        let g = display_graph(&profiler, &profiler.instantiation_graph);
+        let max_instantiations = profiler
+            .quantifier_stats
+            .iter()
+            .map(|cost| cost.instantiations)
+            .max()
+            .unwrap_or(0);
+        let mut app = Self {
+            g,
+            profiler,
+            max_instantiations,
+            visible: None,
+            hidden: HashSet::new(),
+            min_weight: 1,
+            light_edges: Vec::new(),
+            drop_isolated: false,
+            explore: false,
+            expanded: HashSet::new(),
+            menu_node: None,
+            explained: None,
+            stepped: None,
+            layout_seed,
+            layout: GraphLayout::Default,
+            show_histogram: false,
+            center_on: None,
+            fit_view: false,
+            search: String::new(),
+            png_path: "graph.png".to_owned(),
+            pending_png: None,
+            png_status: None,
+            updates,
+        };
+        if collapsed {
+            app.start_exploring();
+        } else {
+            app.apply_layout();
+        }
+        app
+    }
+
+    /// Show `profiler` instead, keeping what is hidden, expanded or focused
+    /// on as far as the new graph still has it.
+    fn replace_profiler(&mut self, profiler: Profiler) {
+        self.max_instantiations = profiler
+            .quantifier_stats
+            .iter()
+            .map(|cost| cost.instantiations)
+            .max()
+            .unwrap_or(0);
+        self.profiler = profiler;
+        let graph = &self.profiler.instantiation_graph;
+        if self
+            .explained
+            .is_some_and(|key| !graph.nodes.contains(&key))
+        {
+            self.explained = None;
+        }
+        if self.explore {
+            self.visible = Some(self.explored());
+        }
+        self.rebuild();
+    }
+
+    /// Show only the roots, to be expanded by clicking them.
+    fn start_exploring(&mut self) {
+        self.explore = true;
+        self.expanded.clear();
+        self.visible = Some(self.explored());
+        self.rebuild();
+        self.fit_view = true;
+    }
+
+    /// The roots and the successors of every shown node in `expanded`. A
+    /// root is the smallest key of a strongly connected component with no
+    /// edges from outside, so that a graph of cycles has roots too.
+    fn explored(&self) -> HashSet<(u64, usize)> {
+        let graph = &self.profiler.instantiation_graph;
+        let condensation = graph.condensation();
+        let entered: HashSet<usize> = condensation.successors.iter().flatten().copied().collect();
+        let mut shown: HashSet<(u64, usize)> = condensation
+            .components
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| !entered.contains(i))
+            .filter_map(|(_, nodes)| nodes.first().copied())
+            .collect();
+        let mut queue: Vec<(u64, usize)> = shown.iter().copied().collect();
+        while let Some(node) = queue.pop() {
+            if !self.expanded.contains(&node) {
+                continue;
+            }
+            for succ in graph.successors(node) {
+                if shown.insert(succ) {
+                    queue.push(succ);
+                }
+            }
+        }
+        shown
+    }
+
+    /// Show the successors of `key` if they are hidden, hide them otherwise.
+    fn toggle_expanded(&mut self, key: (u64, usize)) {
+        if !self.expanded.remove(&key) {
+            self.expanded.insert(key);
+        }
+        self.visible = Some(self.explored());
+        self.rebuild();
+    }
+
+    fn apply_layout(&mut self) {
+        match self.layout {
+            GraphLayout::Default => self.seed_layout(),
+            GraphLayout::Layered => self.layered_layout(),
+        }
+    }
+
+    /// Place the displayed nodes by `InstantiationGraph::layers`.
+    fn layered_layout(&mut self) {
+        const SPACING: f32 = 80.;
+        let keep: HashSet<(u64, usize)> = self
+            .g
+            .nodes_iter()
+            .filter_map(|(_, n)| n.data().map(|d| d.key))
+            .collect();
+        let layers = self
+            .profiler
+            .instantiation_graph
+            .restrict_to(&keep)
+            .layers();
+        let placed: Vec<_> = self
+            .g
+            .nodes_iter()
+            .filter_map(|(idx, n)| n.data().map(|d| (idx, layers[&d.key])))
+            .collect();
+        for (idx, (column, row)) in placed {
+            let loc = Vec2::new(column as f32 * SPACING * 2., row as f32 * SPACING);
+            if let Some(node) = self.g.node_mut(idx) {
+                node.set_location(loc);
+            }
+        }
+    }
+
+    /// Replace egui_graphs' random initial positions with ones drawn from
+    /// `layout_seed`, if given. Nodes are created in key order, so the same
+    /// graph and seed always give the same picture.
+    fn seed_layout(&mut self) {
+        let Some(seed) = self.layout_seed else {
+            return;
+        };
+        let mut rng = SplitMix64(seed);
+        let indices: Vec<_> = self.g.nodes_iter().map(|(idx, _)| idx).collect();
+        let side = (indices.len() as f32).sqrt().max(1.) * 50.;
+        for idx in indices {
+            let loc = Vec2::new(rng.next_f32() * side, rng.next_f32() * side);
+            if let Some(node) = self.g.node_mut(idx) {
+                node.set_location(loc);
+            }
+        }
+    }
+
+    fn rebuild(&mut self) {
+        let graph = &self.profiler.instantiation_graph;
+        let mut keep = match &self.visible {
+            Some(visible) => visible.clone(),
+            None => graph.nodes.clone(),
+        };
+        keep.retain(|k| !self.hidden.contains(k));
+        let mut shown = graph.restrict_to(&keep);
+        if self.drop_isolated {
+            shown = shown.without_edges_below(self.min_weight);
+        }
+        self.g = display_graph(&self.profiler, &shown);
+        self.light_edges.clear();
+        self.apply_min_weight();
+        self.apply_layout();
+        self.apply_search();
+    }
+
+    /// Take the edges lighter than `min_weight` out of `g` and put back those
+    /// taken out before that are heavy enough now. The nodes stay where they
+    /// are, so moving the threshold does not need a rebuild.
+    fn apply_min_weight(&mut self) {
+        let min_weight = self.min_weight;
+        let weight = |edge: &Edge<u64>| edge.data().copied().unwrap_or(1);
+        let (restored, light) = std::mem::take(&mut self.light_edges)
+            .into_iter()
+            .partition(|(_, _, edge)| weight(edge) >= min_weight);
+        self.light_edges = light;
+        for (src, tgt, edge) in restored {
+            self.g.g.add_edge(src, tgt, edge);
+        }
+        let lighter: Vec<_> = self
+            .g
+            .edges_iter()
+            .filter(|(_, edge)| weight(edge) < min_weight)
+            .map(|(idx, _)| idx)
+            .collect();
+        for idx in lighter {
+            let Some((src, tgt)) = self.g.edge_endpoints(idx) else {
+                continue;
+            };
+            if let Some(mut edge) = self.g.g.remove_edge(idx) {
+                edge.set_selected(false);
+                self.light_edges.push((src, tgt, edge));
+            }
+        }
+    }
+
+    /// Select the nodes whose name contains `search`, ignoring case, and dim
+    /// the others. Center on the first exact match, if any. An empty search
+    /// matches nothing and dims nothing.
+    fn apply_search(&mut self) {
+        let needle = self.search.to_lowercase();
+        let indices: Vec<_> = self.g.nodes_iter().map(|(idx, _)| idx).collect();
+        for idx in indices {
+            let Some(node) = self.g.node_mut(idx) else {
+                continue;
+            };
+            let name = node
+                .data()
+                .map(|d| d.name.to_lowercase())
+                .unwrap_or_default();
+            let matches = !needle.is_empty() && name.contains(&needle);
+            node.set_selected(matches);
+            if matches && name == needle && self.center_on.is_none() {
+                self.center_on = Some(node.location());
+            }
+            if let Some(d) = node.data_mut() {
+                d.dimmed = !needle.is_empty() && !matches;
+            }
+        }
+    }
+
+    /// Zoom and pan `meta` so that every displayed node lies within `panel`,
+    /// with some room to spare for the node labels.
+    fn fit_to(&self, meta: &mut Metadata, panel: Rect) {
+        const MARGIN: f32 = 100.;
+        let points: Vec<egui::Pos2> = self
+            .g
+            .nodes_iter()
+            .map(|(_, n)| n.location().to_pos2())
+            .collect();
+        if points.is_empty() {
+            return;
+        }
+        let bounds = Rect::from_points(&points);
+        meta.zoom = (panel.width() / (bounds.width() + MARGIN))
+            .min(panel.height() / (bounds.height() + MARGIN));
+        meta.pan = panel.center().to_vec2() - bounds.center().to_vec2() * meta.zoom;
+    }
+
+    fn node_data_at(&self, meta: &Metadata, pos: egui::Pos2) -> Option<&GuiNode> {
+        let style = SettingsStyle::default();
+        self.g
+            .nodes_iter()
+            .find(|(_, n)| {
+                let centre = n.screen_location(meta).to_pos2();
+                centre.distance(pos) <= n.screen_radius(meta, &style)
+            })
+            .and_then(|(_, n)| n.data())
+    }
+
+    fn node_at(&self, meta: &Metadata, pos: egui::Pos2) -> Option<(u64, usize)> {
+        self.node_data_at(meta, pos).map(|d| d.key)
+    }
+
+    fn apply(&mut self, ctx: &Context, action: NodeAction, key: (u64, usize)) {
+        let graph = &self.profiler.instantiation_graph;
+        match action {
+            NodeAction::FocusNeighborhood => {
+                let mut keep: HashSet<(u64, usize)> = graph.successors(key).collect();
+                keep.extend(graph.predecessors(key));
+                keep.insert(key);
+                self.visible = Some(keep);
+                self.rebuild();
+            }
+            NodeAction::CopyName => {
+                let name = graph.names[&key].clone();
+                ctx.output_mut(|o| o.copied_text = name);
+            }
+            NodeAction::Hide => {
+                self.hidden.insert(key);
+                self.rebuild();
+            }
+            NodeAction::OnlyDescendants => {
+                self.visible = Some(graph.descendants(key));
+                self.rebuild();
+            }
+            NodeAction::Explain => self.explained = Some(key),
+        }
+    }
+
+    /// Select every displayed node of `quantifier` and center the view on
+    /// the first of them.
+    fn select_quantifier(&mut self, quantifier: &str) {
+        let indices: Vec<_> = self.g.nodes_iter().map(|(idx, _)| idx).collect();
+        self.center_on = None;
+        for idx in indices {
+            if let Some(node) = self.g.node_mut(idx) {
+                let matches = node.data().is_some_and(|d| d.name == quantifier);
+                node.set_selected(matches);
+                if matches && self.center_on.is_none() {
+                    self.center_on = Some(node.location());
+                }
+            }
+        }
+    }
+
+    /// Select only the node `key`, if displayed, and center the view on it.
+    fn select_node(&mut self, key: (u64, usize)) {
+        let indices: Vec<_> = self.g.nodes_iter().map(|(idx, _)| idx).collect();
+        for idx in indices {
+            if let Some(node) = self.g.node_mut(idx) {
+                let matches = node.data().is_some_and(|d| d.key == key);
+                node.set_selected(matches);
+                if matches {
+                    self.center_on = Some(node.location());
+                }
+            }
+        }
+    }
+
+    /// The displayed nodes in the order `n` and `p` step through them: by
+    /// the rank of their quantifier, and of the nodes of one quantifier the
+    /// one that led to the most instantiations (outgoing edges) first.
+    fn ranked_nodes(&self) -> Vec<(u64, usize)> {
+        let rank: HashMap<&str, usize> = self
+            .profiler
+            .quantifier_stats
+            .iter()
+            .enumerate()
+            .map(|(i, cost)| (cost.quant.as_str(), i))
+            .collect();
+        let mut nodes: Vec<(usize, Reverse<usize>, (u64, usize))> = self
+            .g
+            .nodes_iter()
+            .filter_map(|(_, n)| n.data())
+            .map(|d| {
+                let rank = rank.get(d.name.as_str()).copied().unwrap_or(usize::MAX);
+                (rank, Reverse(d.out_degree), d.key)
+            })
+            .collect();
+        nodes.sort();
+        nodes.into_iter().map(|(_, _, key)| key).collect()
+    }
+
+    /// Select, center on and explain the node after (or before) the one
+    /// last stepped to in `ranked_nodes`, wrapping around at either end.
+    fn step_ranked(&mut self, forward: bool) {
+        let ranked = self.ranked_nodes();
+        if ranked.is_empty() {
+            return;
+        }
+        let last = self
+            .stepped
+            .and_then(|key| ranked.iter().position(|k| *k == key));
+        let next = match last {
+            Some(i) if forward => (i + 1) % ranked.len(),
+            Some(i) => (i + ranked.len() - 1) % ranked.len(),
+            None if forward => 0,
+            None => ranked.len() - 1,
+        };
+        let key = ranked[next];
+        self.select_node(key);
+        self.stepped = Some(key);
+        self.explained = Some(key);
+    }
+
+    /// Bar chart of the most instantiated quantifiers; clicking a bar selects
+    /// the quantifier in the graph.
+    fn histogram_panel(&mut self, ctx: &Context) {
+        const BARS: usize = 20;
+        if !self.show_histogram {
+            return;
+        }
+        let stats = &self.profiler.quantifier_stats;
+        let bars: Vec<Bar> = stats
+            .iter()
+            .take(BARS)
+            .enumerate()
+            .map(|(i, cost)| {
+                Bar::new(i as f64, cost.instantiations as f64)
+                    .name(truncate_name(&cost.quant, 40))
+                    .width(0.8)
+            })
+            .collect();
+        let mut clicked = None;
+        egui::SidePanel::right("histogram")
+            .resizable(true)
+            .default_width(320.)
+            .show(ctx, |ui| {
+                ui.heading("Most instantiated quantifiers");
+                Plot::new("histogram")
+                    .allow_drag(false)
+                    .allow_zoom(false)
+                    .allow_scroll(false)
+                    .show(ui, |plot_ui| {
+                        plot_ui.bar_chart(BarChart::new(bars).name("instantiations"));
+                        if plot_ui.plot_clicked() {
+                            clicked = plot_ui.pointer_coordinate().map(|p| p.x.round());
+                        }
+                    });
+            });
+        let Some(x) = clicked else {
+            return;
+        };
+        if x >= 0. && (x as usize) < BARS {
+            if let Some(cost) = stats.get(x as usize) {
+                let quantifier = cost.quant.clone();
+                self.select_quantifier(&quantifier);
+            }
+        }
+    }
+
+    fn explain_window(&mut self, ctx: &Context) {
+        let Some(key) = self.explained else {
+            return;
+        };
+        let graph = &self.profiler.instantiation_graph;
+        let lifespans = &self.profiler.lifespans;
+        let mut open = true;
+        egui::Window::new("Explain")
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(format!("Quantifier: {}", graph.names[&key]));
+                ui.label(format!("Instantiation: {:#x} (version {})", key.0, key.1));
+                let stats = &self.profiler.quantifier_stats;
+                if let Some(cost) = stats.iter().find(|c| c.quant == graph.names[&key]) {
+                    ui.label(format!(
+                        "{} instantiations ({:.1}% of the total), cost {}",
+                        cost.instantiations,
+                        percentage(cost.instantiations, self.profiler.total_instantiations()),
+                        cost.cost
+                    ));
+                }
+                if let Some((first, last)) = lifespans.get(&graph.names[&key]) {
+                    ui.label(format!(
+                        "Quantifier active from version {} to {}",
+                        first, last
+                    ));
+                }
+                let successors: BTreeSet<&String> =
+                    graph.successors(key).map(|k| &graph.names[&k]).collect();
+                let predecessors: BTreeSet<&String> =
+                    graph.predecessors(key).map(|k| &graph.names[&k]).collect();
+                ui.separator();
+                ui.label("Triggered by instantiations of:");
+                for name in predecessors {
+                    ui.monospace(name);
+                }
+                ui.separator();
+                ui.label("Produced terms that triggered:");
+                for name in successors {
+                    ui.monospace(name);
+                }
+            });
+        if !open {
+            self.explained = None;
+        }
+    }
+}
+
+/// Write `image` to `path` as PNG.
+fn save_png(image: &egui::ColorImage, path: &str) -> anyhow::Result<()> {
+    let bytes: Vec<u8> = image.pixels.iter().flat_map(|p| p.to_array()).collect();
+    image::save_buffer(
+        path,
+        &bytes,
+        image.size[0] as u32,
+        image.size[1] as u32,
+        image::ColorType::Rgba8,
+    )?;
+    Ok(())
+}
+
+/// SplitMix64, which is plenty for scattering nodes reproducibly.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform in [0, 1)
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+impl App for BasicApp {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        if let Some(updates) = &self.updates {
+            // Only the latest matters if several arrived since the last frame
+            if let Some(profiler) = updates.try_iter().last() {
+                self.replace_profiler(profiler);
+            }
+            ctx.request_repaint_after(Duration::from_millis(500));
+        }
+        let mut save_png = false;
+        egui::TopBottomPanel::top("controls").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Show all").clicked() {
+                    self.visible = None;
+                    self.hidden.clear();
+                    self.explore = false;
+                    self.rebuild();
+                }
+                if ui.checkbox(&mut self.explore, "Expand on click").changed() {
+                    if self.explore {
+                        self.start_exploring();
+                    } else {
+                        self.visible = None;
+                        self.rebuild();
+                    }
+                }
+                if ui.button("Fit view (F)").clicked() {
+                    self.fit_view = true;
+                }
+                if ui.button("Re-layout").clicked() {
+                    self.rebuild();
+                    self.fit_view = true;
+                }
+                ui.checkbox(&mut self.show_histogram, "Histogram");
+                let search = ui.add(
+                    egui::TextEdit::singleline(&mut self.search)
+                        .hint_text("Search quantifiers")
+                        .desired_width(200.),
+                );
+                if search.changed() {
+                    self.apply_search();
+                }
+                ui.add(egui::TextEdit::singleline(&mut self.png_path).desired_width(120.));
+                save_png = ui.button("Save PNG").clicked();
+                if let Some(status) = &self.png_status {
+                    ui.label(status);
+                }
+                ui.label("Right-click a node for more actions, N/P to step through the ranking");
+                ui.separator();
+                // Inverse of `heat`, for the middle of the scale
+                let max = self.max_instantiations;
+                let mid = ((max as f64).ln_1p() / 2.).exp_m1().round() as u64;
+                ui.label("Instantiations (log scale):");
+                ui.colored_label(heat_color(0.), "■ 0");
+                ui.colored_label(heat_color(0.5), format!("■ {}", mid));
+                ui.colored_label(heat_color(1.), format!("■ {}", max));
+            });
+        });
+        egui::SidePanel::left("layout").show(ctx, |ui| {
+            ui.heading("Layout");
+            let before = self.layout;
+            egui::ComboBox::from_id_source("layout")
+                .selected_text(self.layout.label())
+                .show_ui(ui, |ui| {
+                    for layout in GraphLayout::ALL {
+                        ui.selectable_value(&mut self.layout, layout, layout.label());
+                    }
+                });
+            if self.layout != before {
+                self.rebuild();
+                self.fit_view = true;
+            }
+            ui.separator();
+            ui.heading("Edges");
+            let heaviest = self
+                .profiler
+                .instantiation_graph
+                .weights
+                .values()
+                .copied()
+                .max()
+                .unwrap_or(1)
+                .max(1);
+            let slider = egui::Slider::new(&mut self.min_weight, 1..=heaviest)
+                .logarithmic(true)
+                .text("Min. weight");
+            if ui.add(slider).changed() {
+                if self.drop_isolated {
+                    self.rebuild();
+                } else {
+                    self.apply_min_weight();
+                }
+            }
+            if ui
+                .checkbox(&mut self.drop_isolated, "Drop nodes left without edges")
+                .changed()
+            {
+                self.rebuild();
+            }
+        });
+        self.histogram_panel(ctx);
+        if !ctx.wants_keyboard_input() {
+            let (fit, next, previous) = ctx.input(|i| {
+                (
+                    i.key_pressed(egui::Key::F),
+                    i.key_pressed(egui::Key::N),
+                    i.key_pressed(egui::Key::P),
+                )
+            });
+            if fit {
+                self.fit_view = true;
+            }
+            if next || previous {
+                self.step_ranked(next);
+            }
+        }
+
+        let mut action = None;
+        let mut toggled = None;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if let Some(loc) = self.center_on.take() {
+                let mut meta = Metadata::get(ui);
+                meta.pan = ui.max_rect().center().to_vec2() - loc * meta.zoom;
+                meta.store_into_ui(ui);
+            }
+            if save_png {
+                // The pixels only exist once this frame has been rendered,
+                // see `post_rendering`
+                self.pending_png = Some((ui.max_rect(), ctx.pixels_per_point()));
+                frame.request_screenshot();
+            }
+            if std::mem::take(&mut self.fit_view) {
+                let mut meta = Metadata::get(ui);
+                self.fit_to(&mut meta, ui.max_rect());
+                meta.store_into_ui(ui);
+            }
+            let response = ui.add(
+                &mut GraphView::new(&mut self.g)
+                    .with_interactions(
+                        &SettingsInteraction::default()
+                            .with_dragging_enabled(true)
+                            .with_selection_enabled(true),
+                    )
+                    .with_custom_node_draw(|ctx, n, state, l| {
+                        // Widest a node may grow to fit its label, in node radii
+                        const MAX_WIDTH: f32 = 10.;
+                        let node_centre_loc = n.screen_location(state.meta).to_pos2();
+                        let rad = n.screen_radius(state.meta, state.style);
+                        let Some(d) = n.data() else {
+                            return;
+                        };
+
+                        // first lay out the label, shortening the name (but not
+                        // the count) if it would make the node too wide
+                        let mut color = ctx.style().visuals.text_color();
+                        if d.dimmed {
+                            color = color.gamma_multiply(0.2);
+                        }
+                        let layout = |text: String| {
+                            ctx.fonts(|f| {
+                                f.layout_no_wrap(
+                                    text,
+                                    FontId::new(rad, FontFamily::Monospace),
+                                    color,
+                                )
+                            })
+                        };
+                        let count = format!(" (×{})", d.instantiations);
+                        let mut galley = layout(format!("{}{}", d.name, count));
+                        if galley.size().x > rad * MAX_WIDTH {
+                            // Monospace, so every character is equally wide
+                            let chars = d.name.chars().count() + count.chars().count();
+                            let fit = (rad * MAX_WIDTH / (galley.size().x / chars as f32)) as usize;
+                            let room = fit.saturating_sub(count.chars().count()).max(2);
+                            let name = truncate_name(&d.name, room);
+                            galley = layout(format!("{}{}", name, count));
+                        }
+
+                        // then the rect around it
+                        let size =
+                            Vec2::new((galley.size().x + rad / 2.).max(rad * 1.5), rad * 1.5);
+                        let rect = Rect::from_center_size(node_centre_loc, size);
+                        // Keep egui_graphs' highlight for selected nodes
+                        let stroke_color = if d.dimmed {
+                            heat_color(d.heat).gamma_multiply(0.2)
+                        } else if !n.selected() {
+                            heat_color(d.heat)
+                        } else {
+                            n.color(ctx)
+                        };
+                        let shape_rect = Shape::rect_stroke(
+                            rect,
+                            Rounding::default(),
+                            Stroke::new(1., stroke_color),
+                        );
+                        l.add(shape_rect);
+
+                        // we need to offset label by half its size to place it in the center of the rect
+                        let offset = Vec2::new(-galley.size().x / 2., -galley.size().y / 2.);
+                        let shape_label = TextShape::new(node_centre_loc + offset, galley);
+                        l.add(shape_label);
+                    }),
+            );
+
+            if let Some(pos) = response.hover_pos() {
+                let meta = Metadata::get(ui);
+                if let Some(d) = self.node_data_at(&meta, pos) {
+                    egui::show_tooltip_at_pointer(ctx, egui::Id::new("node stats"), |ui| {
+                        ui.strong(truncate_name(&d.name, 60));
+                        ui.label(format!(
+                            "{} instantiations ({:.1}% of the total), cost {}",
+                            d.instantiations, d.share, d.cost
+                        ));
+                        ui.label(format!(
+                            "{} incoming, {} outgoing edges",
+                            d.in_degree, d.out_degree
+                        ));
+                    });
+                }
+            }
+            if self.explore && response.clicked() {
+                let meta = Metadata::get(ui);
+                toggled = response
+                    .interact_pointer_pos()
+                    .and_then(|pos| self.node_at(&meta, pos));
+            }
+            if response.secondary_clicked() {
+                let meta = Metadata::get(ui);
+                self.menu_node = response
+                    .interact_pointer_pos()
+                    .and_then(|pos| self.node_at(&meta, pos));
+            }
+            let names = &self.profiler.instantiation_graph.names;
+            let menu_node = self.menu_node;
+            response.context_menu(|ui| match menu_node {
+                Some(key) => {
+                    ui.label(truncate_name(&names[&key], 40));
+                    ui.separator();
+                    for a in NodeAction::ALL {
+                        if ui.button(a.label()).clicked() {
+                            action = Some((a, key));
+                            ui.close_menu();
+                        }
+                    }
+                }
+                None => {
+                    ui.label("No node here");
+                }
+            });
+        });
+
+        if let Some((a, key)) = action {
+            self.apply(ctx, a, key);
+        }
+        if let Some(key) = toggled {
+            self.toggle_expanded(key);
+        }
+        self.explain_window(ctx);
+    }
+
+    fn post_rendering(&mut self, _: [u32; 2], frame: &eframe::Frame) {
+        let Some((rect, pixels_per_point)) = self.pending_png else {
+            return;
+        };
+        // Keep waiting if the screenshot is not in this frame yet
+        let Some(screenshot) = frame.screenshot() else {
+            return;
+        };
+        self.pending_png = None;
+        let image = screenshot.region(&rect, Some(pixels_per_point));
+        self.png_status = Some(match save_png(&image, &self.png_path) {
+            Ok(()) => format!("Saved {}", self.png_path),
+            Err(e) => format!("Could not save {}: {}", self.png_path, e),
+        });
+    }
+}
+
+/// Whether a window can plausibly be opened. On Linux and the BSDs eframe
+/// needs an X11 or Wayland display; elsewhere we assume one is present.
+fn display_available() -> bool {
+    if cfg!(any(target_os = "windows", target_os = "macos")) {
+        return true;
+    }
+    ["DISPLAY", "WAYLAND_DISPLAY"]
+        .iter()
+        .any(|var| std::env::var_os(var).is_some_and(|v| !v.is_empty()))
+}
+
+/// Open the GUI on `profiler`, switching to every profile received on
+/// `updates` if given. Without a display, or if the window cannot be
+/// created, only warn: the stats have been printed already.
+pub fn run(
+    profiler: Profiler,
+    layout_seed: Option<u64>,
+    collapsed: bool,
+    updates: Option<Receiver<Profiler>>,
+) {
+    if !display_available() {
+        eprintln!("warning: no display found (neither DISPLAY nor WAYLAND_DISPLAY is set), not starting the GUI");
+        eprintln!("hint: use the `dot` subcommand to export the graph when running headless");
+        return;
+    }
+
+    let native_options = eframe::NativeOptions::default();
+    if let Err(e) = run_native(
+        "SMT quantifier instantiations graph",
+        native_options,
+        Box::new(move |cc| Box::new(BasicApp::new(cc, profiler, layout_seed, collapsed, updates))),
+    ) {
+        eprintln!("warning: could not start the GUI: {}", e);
+        eprintln!("hint: use the `dot` subcommand to export the graph when running headless");
+    }
+}
@@ -0,0 +1,4014 @@
+//! Quantifier instantiation profiling for Z3 trace logs.
+//!
+//! `Profiler` parses a trace (from a path or any `BufRead`) and keeps the
+//! per-quantifier costs and the graph of which instantiations triggered
+//! which; the rest of this crate are analyses and exports over those.
+//!
+//! Nothing here needs the GUI, so depending on this crate with
+//! `default-features = false` leaves out eframe and egui.
+
+use anyhow::Error;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fs::File;
+use std::io::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use petgraph::{
+    graphmap::DiGraphMap,
+    stable_graph::StableGraph,
+    Directed,
+};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use z3tracer::{
+    model::QuantCost,
+    syntax::{Ident, MatchedTerm, QiFrame, QiKey, QuantInstantiation, Term},
+    Model, ModelConfig,
+};
+
+fn model_config(options: &ProfilerOptions, show_progress_bar: bool) -> ModelConfig {
+    let mut model_config = ModelConfig::default();
+    model_config.parser_config.skip_z3_version_check = !options.check_z3_version;
+    // `process_reader` skips bad lines itself, so that it can count them
+    // (or with `strict_lines` fail on them)
+    model_config.parser_config.ignore_invalid_lines = false;
+    model_config.parser_config.show_progress_bar = show_progress_bar && !options.quiet;
+    model_config.skip_log_consistency_checks = !options.consistency_checks;
+    model_config.log_term_equalities = options.log_equalities;
+    model_config.log_internal_term_equalities = false;
+    model_config
+}
+
+/// Start of every gzip stream.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// `ProfilerOptions::why_not` reads the equalities only logged with
+/// `ProfilerOptions::log_equalities`.
+fn check_why_not(options: &ProfilerOptions) -> anyhow::Result<()> {
+    if !options.why_not.is_empty() && !options.log_equalities {
+        anyhow::bail!(
+            "explaining why `{}` did not fire needs the trace's equalities; \
+             turn on equality logging (--log-equalities)",
+            options.why_not.join("`, `")
+        );
+    }
+    Ok(())
+}
+
+/// A buffered reader over the trace at `path`, decompressing it on the fly
+/// if it is gzipped.
+fn open_trace(path: &std::path::Path) -> std::io::Result<Box<dyn BufRead>> {
+    let mut file = std::io::BufReader::new(File::open(path)?);
+    if file.fill_buf()?.starts_with(&GZIP_MAGIC) {
+        Ok(Box::new(std::io::BufReader::new(
+            flate2::read::GzDecoder::new(file),
+        )))
+    } else {
+        Ok(Box::new(file))
+    }
+}
+
+/// Typical length of a trace line in bytes, to estimate the number of lines
+/// for the progress bar from the size of the trace.
+const AVG_LINE_LEN: u64 = 40;
+
+/// Roughly how many lines the trace at `path` has, without reading it: from
+/// its size, or for a gzipped trace from the uncompressed size in the gzip
+/// trailer. That is only kept modulo 4 GiB, so for bigger traces the
+/// progress bar is off, but the trace is still read once.
+fn estimate_line_count(path: &std::path::Path) -> std::io::Result<usize> {
+    let mut file = File::open(path)?;
+    let mut size = file.metadata()?.len();
+    let mut magic = [0; GZIP_MAGIC.len()];
+    if size >= 4 && file.read_exact(&mut magic).is_ok() && magic == GZIP_MAGIC {
+        let mut trailer = [0; 4];
+        file.seek(std::io::SeekFrom::End(-4))?;
+        file.read_exact(&mut trailer)?;
+        size = u32::from_le_bytes(trailer).into();
+    }
+    Ok((size / AVG_LINE_LEN) as usize)
+}
+
+/// A `path` of `-` reads the trace from stdin, which is buffered in memory
+/// so the lines can be counted first. A file is only read once, with a
+/// line count estimated from its size, see `estimate_line_count`.
+fn process_file(
+    path: &std::path::Path,
+    options: &ProfilerOptions,
+    timings: &mut Timings,
+    report: &mut ConsistencyReport,
+    problems: &mut ParseProblems,
+) -> anyhow::Result<Model> {
+    let start = Instant::now();
+    if path == std::path::Path::new("-") {
+        let mut buf = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut buf)?;
+        let line_count = buf.as_slice().lines().count();
+        timings.line_count = start.elapsed();
+        return process_reader(
+            std::io::Cursor::new(buf),
+            Some("<stdin>".to_owned()),
+            Some(line_count),
+            options,
+            timings,
+            report,
+            problems,
+        );
+    }
+    let line_count = estimate_line_count(path)?;
+    let file = open_trace(path)?;
+    timings.line_count = start.elapsed();
+
+    let e = Error::new(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        "Invalid path",
+    ));
+
+    let p: Option<String> = match path.to_str() {
+        Some(pa) => Some(pa.to_owned()),
+        None => return Err(e),
+    };
+
+    process_reader(
+        file,
+        p,
+        Some(line_count),
+        options,
+        timings,
+        report,
+        problems,
+    )
+}
+
+/// Without a line count there is no total for the progress bar, so it is
+/// turned off.
+fn process_reader<R: BufRead>(
+    reader: R,
+    path_name: Option<String>,
+    line_count: Option<usize>,
+    options: &ProfilerOptions,
+    timings: &mut Timings,
+    report: &mut ConsistencyReport,
+    problems: &mut ParseProblems,
+) -> anyhow::Result<Model> {
+    let mut model = Model::new(model_config(options, line_count.is_some()));
+    let line_count = line_count.unwrap_or(0);
+
+    let start = Instant::now();
+    // z3tracer stops at the first line it cannot handle, so resume after the
+    // offending line each time, counting (and with consistency checks,
+    // collecting) everything that was skipped.
+    let mut reader = Tally::new(
+        with_version_hint(reader, options.z3_version.as_deref())?,
+        !options.why_not.is_empty(),
+    );
+    loop {
+        let before = reader.consumed;
+        match model.process(path_name.clone(), &mut reader, line_count) {
+            Ok(()) => break,
+            Err(e) => {
+                if options.strict_lines {
+                    return Err(e.into());
+                }
+                if reader.consumed == before && !options.consistency_checks {
+                    // Not a bad line: the parser could not read anything
+                    return Err(e.into());
+                }
+                problems.ignored_lines += 1;
+                if options.consistency_checks {
+                    report.record(e.to_string());
+                }
+                if reader.consumed == before || reader.fill_buf()?.is_empty() {
+                    break;
+                }
+            }
+        }
+    }
+    timings.parse = start.elapsed();
+    problems.truncation = reader.shape.truncation();
+    problems.equalities = reader.shape.equalities.take().unwrap_or_default();
+    if options.verbose {
+        eprintln!(
+            "Parsed {} instantiations in {:.1}s",
+            model.instantiations().len(),
+            timings.parse.as_secs_f64()
+        );
+    }
+    Ok(model)
+}
+
+/// Prefix the trace with a `[tool-version]` line for `hint` unless it
+/// already starts with one, so z3tracer handles the trace as written by
+/// that version. The trace's own version line always wins.
+fn with_version_hint<R: BufRead>(
+    mut reader: R,
+    hint: Option<&str>,
+) -> std::io::Result<std::io::Chain<std::io::Cursor<Vec<u8>>, R>> {
+    let mut prefix = Vec::new();
+    if let Some(hint) = hint {
+        if !reader.fill_buf()?.starts_with(b"[tool-version]") {
+            prefix = format!("[tool-version] Z3 {}\n", hint).into_bytes();
+        }
+    }
+    Ok(std::io::Cursor::new(prefix).chain(reader))
+}
+
+/// What `process_reader` noticed about the trace besides its contents, see
+/// the `Profiler` fields of the same names.
+#[derive(Debug, Default)]
+struct ParseProblems {
+    ignored_lines: usize,
+    truncation: Option<String>,
+    /// The `[eq-expl]` lines, which z3tracer does not keep; only collected
+    /// for `ProfilerOptions::why_not`
+    equalities: Vec<String>,
+}
+
+/// Counts the bytes the wrapped reader has handed out, to tell whether the
+/// parser made any progress, and watches them for signs of a cut-off trace.
+struct Tally<R> {
+    inner: R,
+    consumed: u64,
+    shape: TraceShape,
+}
+
+impl<R> Tally<R> {
+    fn new(inner: R, keep_equalities: bool) -> Self {
+        Tally {
+            inner,
+            consumed: 0,
+            shape: TraceShape {
+                equalities: keep_equalities.then(Vec::new),
+                ..TraceShape::default()
+            },
+        }
+    }
+}
+
+impl<R: Read> Read for Tally<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.consumed += n as u64;
+        self.shape.observe(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<R: BufRead> BufRead for Tally<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.consumed += amt as u64;
+        // Still buffered, so this does no I/O
+        if let Ok(buf) = self.inner.fill_buf() {
+            self.shape.observe(&buf[..amt.min(buf.len())]);
+        }
+        self.inner.consume(amt)
+    }
+}
+
+/// Which instantiation records a trace opened and closed, and how it ended.
+#[derive(Debug, Default)]
+struct TraceShape {
+    /// Start of the current line, enough to recognize the record kind
+    line_start: Vec<u8>,
+    instances: u64,
+    ends_of_instances: u64,
+    /// Whether anything was read and the last byte was not a newline
+    mid_line: bool,
+    /// Complete `[eq-expl]` lines, if they are kept
+    equalities: Option<Vec<String>>,
+    /// The current line so far while it may be an `[eq-expl]` one
+    line: Vec<u8>,
+}
+
+impl TraceShape {
+    const PREFIX_LEN: usize = 20;
+    const EQ_EXPL: &'static [u8] = b"[eq-expl]";
+
+    fn observe(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            if b == b'\n' {
+                if self.line_start.starts_with(b"[instance]") {
+                    self.instances += 1;
+                } else if self.line_start.starts_with(b"[end-of-instance]") {
+                    self.ends_of_instances += 1;
+                } else if let Some(equalities) = &mut self.equalities {
+                    if self.line.starts_with(Self::EQ_EXPL) {
+                        equalities.push(String::from_utf8_lossy(&self.line).into_owned());
+                    }
+                }
+                self.line_start.clear();
+                self.line.clear();
+                continue;
+            }
+            if self.line_start.len() < Self::PREFIX_LEN {
+                self.line_start.push(b);
+            }
+            let undecided = self.line.len() < Self::EQ_EXPL.len();
+            if self.equalities.is_some() && (undecided || self.line.starts_with(Self::EQ_EXPL)) {
+                self.line.push(b);
+            }
+        }
+        if let Some(&last) = bytes.last() {
+            self.mid_line = last != b'\n';
+        }
+    }
+
+    fn truncation(&self) -> Option<String> {
+        let mut signs = Vec::new();
+        if self.mid_line {
+            signs.push("the last line is incomplete".to_owned());
+        }
+        if self.instances > self.ends_of_instances {
+            signs.push(format!(
+                "{} of {} instantiations never reached `[end-of-instance]`",
+                self.instances - self.ends_of_instances,
+                self.instances
+            ));
+        }
+        (!signs.is_empty()).then(|| signs.join(", "))
+    }
+}
+
+/// Problems found by z3tracer when its consistency checks are enabled.
+#[derive(Debug, Default, Clone)]
+pub struct ConsistencyReport {
+    pub issues: Vec<String>,
+    /// Number of issues found, including those not kept in `issues`
+    pub total: usize,
+}
+
+impl ConsistencyReport {
+    const MAX_KEPT: usize = 1000;
+
+    fn record(&mut self, issue: String) {
+        self.total += 1;
+        if self.issues.len() < Self::MAX_KEPT {
+            self.issues.push(issue);
+        }
+    }
+
+    pub fn print(&self) {
+        if self.total == 0 {
+            println!("Consistency checks passed: the trace looks sound");
+            return;
+        }
+        println!(
+            "Consistency checks found {} issue(s); treat the numbers below with care:",
+            self.total
+        );
+        for issue in &self.issues {
+            println!("  {}", issue);
+        }
+        if self.total > self.issues.len() {
+            println!("  ... and {} more", self.total - self.issues.len());
+        }
+        println!();
+    }
+}
+
+/// Wall-clock time spent in each phase of `Profiler::parse`.
+#[derive(Debug, Default, Clone)]
+pub struct Timings {
+    pub line_count: Duration,
+    pub parse: Duration,
+    pub graph: Duration,
+    pub stats: Duration,
+}
+
+impl Timings {
+    pub fn total(&self) -> Duration {
+        self.line_count + self.parse + self.graph + self.stats
+    }
+
+    /// One line per phase with its share of the total, then the total. The
+    /// line count is for the progress bar: an estimate from the size of a
+    /// trace file, but a pass over the lines of one read from stdin.
+    pub fn render(&self) -> String {
+        let total = self.total().as_secs_f64();
+        let mut out = String::new();
+        for (phase, time) in [
+            ("Line count:", self.line_count),
+            ("Parse:", self.parse),
+            ("Graph:", self.graph),
+            ("Stats:", self.stats),
+        ] {
+            let share = if total > 0. {
+                100. * time.as_secs_f64() / total
+            } else {
+                0.
+            };
+            out.push_str(&format!(
+                "{:<12} {:>10.3}s {:>5.1}%\n",
+                phase,
+                time.as_secs_f64(),
+                share
+            ));
+        }
+        out.push_str(&format!("{:<12} {:>10.3}s\n", "Total:", total));
+        out
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.render());
+    }
+}
+
+/// See `InstantiationGraph::condensation`.
+#[derive(Debug, Clone, Default)]
+pub struct Condensation {
+    /// Every edge between two components points to a later one
+    pub components: Vec<Vec<(u64, usize)>>,
+    /// Index into `components` of every node
+    pub component: HashMap<(u64, usize), usize>,
+    /// Indices of the components each component has an edge to
+    pub successors: Vec<BTreeSet<usize>>,
+}
+
+/// An edge of an `InstantiationGraph`, source first.
+pub type Edge = ((u64, usize), (u64, usize));
+
+/// The nodes each node has an edge to, or from, leaving out those with none.
+pub type Adjacency = HashMap<(u64, usize), HashSet<(u64, usize)>>;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstantiationGraph {
+    pub edges: HashMap<(u64, usize), HashSet<(u64, usize)>>,
+    pub names: HashMap<(u64, usize), String>,
+    pub nodes: HashSet<(u64, usize)>,
+    /// Nodes for `QiFrame::Discovered` (theory/MBQI) instantiations, which
+    /// are only present with `ProfilerOptions::include_discovered`
+    pub discovered: HashSet<(u64, usize)>,
+    /// How many of the target's matched terms the source produced, summed
+    /// over the merged edges once nodes are merged with `collapse`. Edges
+    /// missing here weigh one, so code that only needs adjacency can ignore
+    /// this.
+    pub weights: HashMap<Edge, u64>,
+    /// How many instantiations each node of a `condensed` graph stands for,
+    /// for the nodes that are a cycle; empty for every other graph
+    pub cycles: HashMap<(u64, usize), usize>,
+    /// `edges` turned around, built on first use by
+    /// `InstantiationGraph::reverse_edges`; code changing `edges` in place
+    /// afterwards must call `InstantiationGraph::edges_changed`
+    #[serde(skip)]
+    reverse: OnceLock<Adjacency>,
+}
+
+impl InstantiationGraph {
+    /// The subgraph induced by the nodes in `keep`.
+    pub fn restrict_to(&self, keep: &HashSet<(u64, usize)>) -> InstantiationGraph {
+        let nodes: HashSet<(u64, usize)> = self.nodes.intersection(keep).cloned().collect();
+        let edges = self
+            .edges
+            .iter()
+            .filter(|(src, _)| nodes.contains(src))
+            .map(|(src, tgts)| {
+                let tgts: HashSet<(u64, usize)> =
+                    tgts.iter().filter(|t| nodes.contains(t)).cloned().collect();
+                (*src, tgts)
+            })
+            .filter(|(_, tgts)| !tgts.is_empty())
+            .collect();
+        let names = self
+            .names
+            .iter()
+            .filter(|(k, _)| nodes.contains(k))
+            .map(|(k, name)| (*k, name.clone()))
+            .collect();
+        let discovered = self.discovered.intersection(&nodes).cloned().collect();
+        let cycles = self
+            .cycles
+            .iter()
+            .filter(|(k, _)| nodes.contains(k))
+            .map(|(k, size)| (*k, *size))
+            .collect();
+        let weights = self
+            .weights
+            .iter()
+            .filter(|((src, tgt), _)| nodes.contains(src) && nodes.contains(tgt))
+            .map(|(edge, w)| (*edge, *w))
+            .collect();
+        InstantiationGraph {
+            edges,
+            names,
+            nodes,
+            discovered,
+            weights,
+            cycles,
+            reverse: OnceLock::new(),
+        }
+    }
+
+    /// Add the nodes and edges of `other`, summing the weights of edges in
+    /// both. Nodes with the same key are the same node.
+    pub fn union(&mut self, other: InstantiationGraph) {
+        for (src, tgts) in other.edges {
+            self.edges.entry(src).or_default().extend(tgts);
+        }
+        self.names.extend(other.names);
+        self.nodes.extend(other.nodes);
+        self.discovered.extend(other.discovered);
+        self.cycles.extend(other.cycles);
+        for (edge, w) in other.weights {
+            *self.weights.entry(edge).or_insert(0) += w;
+        }
+        self.edges_changed();
+    }
+
+    /// The graph with every key XORed with `namespace`, so that it can be
+    /// unioned with the graph of another trace without the two sharing nodes.
+    pub fn namespaced(&self, namespace: u64) -> InstantiationGraph {
+        let key = |k: &(u64, usize)| (k.0 ^ namespace, k.1);
+        InstantiationGraph {
+            edges: self
+                .edges
+                .iter()
+                .map(|(src, tgts)| (key(src), tgts.iter().map(key).collect()))
+                .collect(),
+            names: self
+                .names
+                .iter()
+                .map(|(k, name)| (key(k), name.clone()))
+                .collect(),
+            nodes: self.nodes.iter().map(key).collect(),
+            discovered: self.discovered.iter().map(key).collect(),
+            weights: self
+                .weights
+                .iter()
+                .map(|((src, tgt), w)| ((key(src), key(tgt)), *w))
+                .collect(),
+            cycles: self
+                .cycles
+                .iter()
+                .map(|(k, size)| (key(k), *size))
+                .collect(),
+            reverse: OnceLock::new(),
+        }
+    }
+
+    /// The graph without any `QiFrame::Discovered` instantiations.
+    pub fn without_discovered(&self) -> InstantiationGraph {
+        let keep = self.nodes.difference(&self.discovered).cloned().collect();
+        self.restrict_to(&keep)
+    }
+
+    /// Weakly-connected components, largest first, each in key order.
+    pub fn weakly_connected_components(&self) -> Vec<Vec<(u64, usize)>> {
+        let mut undirected: HashMap<(u64, usize), Vec<(u64, usize)>> = HashMap::new();
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                undirected.entry(*src).or_default().push(*tgt);
+                undirected.entry(*tgt).or_default().push(*src);
+            }
+        }
+
+        let mut starts: Vec<&(u64, usize)> = self.nodes.iter().collect();
+        starts.sort();
+        let mut seen = HashSet::new();
+        let mut components = Vec::new();
+        for start in starts {
+            if !seen.insert(*start) {
+                continue;
+            }
+            let mut component = vec![*start];
+            let mut stack = vec![*start];
+            while let Some(node) = stack.pop() {
+                for next in undirected.get(&node).into_iter().flatten() {
+                    if seen.insert(*next) {
+                        component.push(*next);
+                        stack.push(*next);
+                    }
+                }
+            }
+            component.sort();
+            components.push(component);
+        }
+        // Stable, so equally-sized components stay in key order
+        components.sort_by_key(|c| std::cmp::Reverse(c.len()));
+        components
+    }
+
+    /// The subgraph of the `k` largest weakly-connected components, and how
+    /// many components there were in all.
+    pub fn largest_components(&self, k: usize) -> (InstantiationGraph, usize) {
+        let components = self.weakly_connected_components();
+        let keep: HashSet<(u64, usize)> = components.iter().take(k).flatten().cloned().collect();
+        (self.restrict_to(&keep), components.len())
+    }
+
+    /// For every quantifier, the number of edges into and out of its
+    /// instantiations.
+    pub fn degrees_by_quantifier(&self) -> HashMap<&str, (u64, u64)> {
+        let mut degrees: HashMap<&str, (u64, u64)> = HashMap::new();
+        for (src, tgts) in &self.edges {
+            degrees.entry(self.names[src].as_str()).or_default().1 += tgts.len() as u64;
+            for tgt in tgts {
+                degrees.entry(self.names[tgt].as_str()).or_default().0 += 1;
+            }
+        }
+        degrees
+    }
+
+    /// See `InstantiationGraph::weights`.
+    pub fn weight(&self, src: (u64, usize), tgt: (u64, usize)) -> u64 {
+        self.weights.get(&(src, tgt)).copied().unwrap_or(1)
+    }
+
+    /// The graph without the edges that weigh less than `min`, and without
+    /// the nodes left isolated by that. Nodes that had no edges to begin with
+    /// are kept.
+    pub fn without_edges_below(&self, min: u64) -> InstantiationGraph {
+        let mut had_edges = HashSet::new();
+        let mut has_edges = HashSet::new();
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                had_edges.extend([*src, *tgt]);
+                if self.weight(*src, *tgt) >= min {
+                    has_edges.extend([*src, *tgt]);
+                }
+            }
+        }
+        let keep = self
+            .nodes
+            .iter()
+            .filter(|n| !had_edges.contains(*n) || has_edges.contains(*n))
+            .cloned()
+            .collect();
+        let mut graph = self.restrict_to(&keep);
+        let weights = std::mem::take(&mut graph.weights);
+        for (src, tgts) in graph.edges.iter_mut() {
+            tgts.retain(|tgt| weights.get(&(*src, *tgt)).copied().unwrap_or(1) >= min);
+        }
+        graph.edges.retain(|_, tgts| !tgts.is_empty());
+        graph.weights = weights.into_iter().filter(|(_, w)| *w >= min).collect();
+        graph
+    }
+
+    /// Number of edges.
+    pub fn edge_count(&self) -> usize {
+        self.edges.values().map(HashSet::len).sum()
+    }
+
+    /// The nodes in key order, for output that must not depend on hash order.
+    pub fn sorted_nodes(&self) -> Vec<(u64, usize)> {
+        let mut nodes: Vec<(u64, usize)> = self.nodes.iter().copied().collect();
+        nodes.sort();
+        nodes
+    }
+
+    /// The edges in key order, for output that must not depend on hash order.
+    pub fn sorted_edges(&self) -> Vec<Edge> {
+        let mut edges: Vec<Edge> = self
+            .edges
+            .iter()
+            .flat_map(|(src, tgts)| tgts.iter().map(move |tgt| (*src, *tgt)))
+            .collect();
+        edges.sort();
+        edges
+    }
+
+    /// The graph with only its `max` heaviest edges. Of the edges as heavy as
+    /// the lightest one kept, an evenly spaced pick in key order is kept, so
+    /// the same graph always gives the same result. All nodes are kept.
+    pub fn heaviest_edges(&self, max: usize) -> InstantiationGraph {
+        let mut all: Vec<Edge> = self
+            .edges
+            .iter()
+            .flat_map(|(src, tgts)| tgts.iter().map(move |tgt| (*src, *tgt)))
+            .collect();
+        if all.len() <= max {
+            return self.clone();
+        }
+        all.sort_by_key(|(src, tgt)| (std::cmp::Reverse(self.weight(*src, *tgt)), *src, *tgt));
+        let mut kept: Vec<Edge> = Vec::with_capacity(max);
+        if max > 0 {
+            let cutoff = self.weight(all[max - 1].0, all[max - 1].1);
+            kept.extend(all.iter().take_while(|(s, t)| self.weight(*s, *t) > cutoff));
+            let tied: Vec<&Edge> = all
+                .iter()
+                .filter(|(s, t)| self.weight(*s, *t) == cutoff)
+                .collect();
+            let wanted = max - kept.len();
+            kept.extend((0..wanted).map(|i| *tied[i * tied.len() / wanted]));
+        }
+        let mut pruned = InstantiationGraph {
+            edges: HashMap::new(),
+            weights: HashMap::new(),
+            reverse: OnceLock::new(),
+            ..self.clone()
+        };
+        for (src, tgt) in kept {
+            pruned.edges.entry(src).or_default().insert(tgt);
+            if let Some(&weight) = self.weights.get(&(src, tgt)) {
+                pruned.weights.insert((src, tgt), weight);
+            }
+        }
+        pruned
+    }
+
+    /// `start` and every node reachable from it.
+    pub fn descendants(&self, start: (u64, usize)) -> HashSet<(u64, usize)> {
+        self.reachable([start], false, None)
+    }
+
+    /// `starts` and every node reachable from one of them, or with `upstream`
+    /// every node one of them can be reached from, in at most `max_depth`
+    /// edges if given.
+    pub fn reachable(
+        &self,
+        starts: impl IntoIterator<Item = (u64, usize)>,
+        upstream: bool,
+        max_depth: Option<usize>,
+    ) -> HashSet<(u64, usize)> {
+        let adjacency = if upstream {
+            self.reverse_edges()
+        } else {
+            &self.edges
+        };
+        // Breadth-first, so each node is first seen at its smallest depth
+        let mut queue: VecDeque<((u64, usize), usize)> =
+            starts.into_iter().map(|start| (start, 0)).collect();
+        let mut seen: HashSet<(u64, usize)> = queue.iter().map(|(node, _)| *node).collect();
+        while let Some((node, depth)) = queue.pop_front() {
+            if max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            for next in adjacency.get(&node).into_iter().flatten() {
+                if seen.insert(*next) {
+                    queue.push_back((*next, depth + 1));
+                }
+            }
+        }
+        seen
+    }
+
+    /// Merge the instantiations of each quantifier whose versions fall in the
+    /// same window of `window` versions, see `InstantiationGraph::collapse`.
+    pub fn version_windows(&self, window: usize) -> InstantiationGraph {
+        self.collapse(|key, name| (name.to_owned(), key.1 / window))
+    }
+
+    /// Merge all nodes that `group` maps to the same quantifier name and
+    /// bucket. Merged nodes are keyed by the position of their name in sorted
+    /// order and the bucket, and edges are the union of the merged ones,
+    /// with their weights summed.
+    pub fn collapse(
+        &self,
+        group: impl Fn(&(u64, usize), &str) -> (String, usize),
+    ) -> InstantiationGraph {
+        let groups: HashMap<(u64, usize), (String, usize)> = self
+            .nodes
+            .iter()
+            .map(|k| (*k, group(k, &self.names[k])))
+            .collect();
+        let mut names: Vec<&String> = groups.values().map(|(name, _)| name).collect();
+        names.sort();
+        names.dedup();
+        let index: HashMap<&String, u64> = names
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| (name, i as u64))
+            .collect();
+        let key_of = |k: &(u64, usize)| {
+            let (name, bucket) = &groups[k];
+            (index[name], *bucket)
+        };
+
+        let mut collapsed = InstantiationGraph::default();
+        for k in &self.nodes {
+            let key = key_of(k);
+            collapsed.nodes.insert(key);
+            collapsed.names.insert(key, groups[k].0.clone());
+        }
+        // A merged node only counts as discovered if all of its members are
+        collapsed.discovered = collapsed.nodes.clone();
+        for k in self.nodes.difference(&self.discovered) {
+            collapsed.discovered.remove(&key_of(k));
+        }
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                let (a, b) = (key_of(src), key_of(tgt));
+                collapsed.edges.entry(a).or_default().insert(b);
+                *collapsed.weights.entry((a, b)).or_default() += self.weight(*src, *tgt);
+            }
+        }
+        collapsed
+    }
+
+    /// The condensation as a graph of its own, a DAG: every strongly-connected
+    /// component becomes one node, keyed by its smallest member, and the
+    /// edges between two components are merged with their weights summed.
+    /// Components that are a cycle, of several nodes or of one that triggers
+    /// itself, are named by `cycle_label`; the other nodes keep their name.
+    pub fn condensed(&self) -> InstantiationGraph {
+        let Condensation {
+            components,
+            component,
+            ..
+        } = self.condensation();
+        let keys: Vec<(u64, usize)> = components.iter().map(|scc| scc[0]).collect();
+        let mut condensed = InstantiationGraph::default();
+        for (scc, key) in components.iter().zip(&keys) {
+            if scc.len() > 1 || self.edges.get(key).is_some_and(|t| t.contains(key)) {
+                condensed.cycles.insert(*key, scc.len());
+            }
+            let name = match condensed.cycles.get(key) {
+                Some(&size) => cycle_label(scc.iter().map(|k| self.names[k].as_str()), size),
+                None => self.names[key].clone(),
+            };
+            condensed.nodes.insert(*key);
+            condensed.names.insert(*key, name);
+            if scc.iter().all(|k| self.discovered.contains(k)) {
+                condensed.discovered.insert(*key);
+            }
+        }
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                let (a, b) = (component[src], component[tgt]);
+                if a != b {
+                    let (a, b) = (keys[a], keys[b]);
+                    condensed.edges.entry(a).or_default().insert(b);
+                    *condensed.weights.entry((a, b)).or_default() += self.weight(*src, *tgt);
+                }
+            }
+        }
+        condensed
+    }
+
+    /// In key order, so that the algorithms run on it give the same result
+    /// for the same graph.
+    fn graph_map(&self) -> DiGraphMap<(u64, usize), ()> {
+        let mut g = DiGraphMap::new();
+        for node in self.sorted_nodes() {
+            g.add_node(node);
+        }
+        for (src, tgt) in self.sorted_edges() {
+            g.add_edge(src, tgt, ());
+        }
+        g
+    }
+
+    /// Strongly-connected components in reverse topological order, i.e. every
+    /// edge between two components points to one earlier in the list.
+    pub fn sccs(&self) -> Vec<Vec<(u64, usize)>> {
+        petgraph::algo::tarjan_scc(&self.graph_map())
+    }
+
+    /// The strongly-connected components in topological order, each sorted,
+    /// with the edges between them.
+    pub fn condensation(&self) -> Condensation {
+        let mut components = self.sccs();
+        components.reverse();
+        let mut component = HashMap::new();
+        for (i, scc) in components.iter_mut().enumerate() {
+            scc.sort();
+            for node in scc.iter() {
+                component.insert(*node, i);
+            }
+        }
+        let mut successors: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); components.len()];
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                let (a, b) = (component[src], component[tgt]);
+                if a != b {
+                    successors[a].insert(b);
+                }
+            }
+        }
+        Condensation {
+            components,
+            component,
+            successors,
+        }
+    }
+
+    /// Every node, in an order where each edge points forward except those
+    /// within a cycle.
+    pub fn topo_order(&self) -> Vec<(u64, usize)> {
+        self.condensation()
+            .components
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// The nodes `node` has an edge to.
+    pub fn successors(&self, node: (u64, usize)) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.edges.get(&node).into_iter().flatten().copied()
+    }
+
+    /// The nodes with an edge to `node`, from `InstantiationGraph::reverse_edges`.
+    pub fn predecessors(&self, node: (u64, usize)) -> impl Iterator<Item = (u64, usize)> + '_ {
+        self.reverse_edges()
+            .get(&node)
+            .into_iter()
+            .flatten()
+            .copied()
+    }
+
+    /// `edges` with every edge turned around, i.e. the predecessors of each
+    /// node with any. Built once and then kept until `edges_changed`.
+    pub fn reverse_edges(&self) -> &Adjacency {
+        self.reverse.get_or_init(|| {
+            let mut preds = Adjacency::new();
+            for (src, tgts) in &self.edges {
+                for tgt in tgts {
+                    preds.entry(*tgt).or_default().insert(*src);
+                }
+            }
+            preds
+        })
+    }
+
+    /// Drop the cached `reverse_edges`, after changing `edges` in place.
+    pub fn edges_changed(&mut self) {
+        self.reverse = OnceLock::new();
+    }
+
+    /// Cycles in the graph: strongly-connected components with more than one
+    /// node, plus nodes with an edge to themselves. With `ignore_discovered`,
+    /// cycles that only close through a `QiFrame::Discovered` instantiation
+    /// are not reported, as those come from theory reasoning, not E-matching.
+    pub fn matching_loops(&self, ignore_discovered: bool) -> Vec<Vec<(u64, usize)>> {
+        if ignore_discovered && !self.discovered.is_empty() {
+            return self.without_discovered().matching_loops(false);
+        }
+        self.sccs()
+            .into_iter()
+            .filter(|scc| {
+                scc.len() > 1
+                    || self
+                        .edges
+                        .get(&scc[0])
+                        .is_some_and(|tgts| tgts.contains(&scc[0]))
+            })
+            .collect()
+    }
+
+    /// A shortest cycle from `start` back to itself that stays within
+    /// `component`, without repeating `start` at the end; of several, the
+    /// first found visiting successors in key order. Empty if there is none.
+    fn cycle_through(
+        &self,
+        start: (u64, usize),
+        component: &HashSet<(u64, usize)>,
+    ) -> Vec<(u64, usize)> {
+        let mut parent = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            let mut successors: Vec<&(u64, usize)> =
+                self.edges.get(&node).into_iter().flatten().collect();
+            successors.sort();
+            for next in successors {
+                if *next == start {
+                    let mut cycle = vec![node];
+                    while let Some(prev) = parent.get(cycle.last().unwrap()) {
+                        cycle.push(*prev);
+                    }
+                    cycle.reverse();
+                    return cycle;
+                }
+                if component.contains(next) && !parent.contains_key(next) {
+                    parent.insert(*next, node);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        Vec::new()
+    }
+
+    /// Causal generation of every node: the length of the longest path to it
+    /// from a root. All members of a cycle share the generation of the
+    /// earliest of them, so this is well defined for any graph.
+    pub fn generations(&self) -> HashMap<(u64, usize), usize> {
+        let condensation = self.condensation();
+        // Components come after all of their predecessors
+        let mut level = vec![0; condensation.components.len()];
+        for (i, succs) in condensation.successors.iter().enumerate() {
+            for s in succs {
+                level[*s] = level[*s].max(level[i] + 1);
+            }
+        }
+        condensation
+            .component
+            .into_iter()
+            .map(|(node, c)| (node, level[c]))
+            .collect()
+    }
+
+    /// Quantifiers with an edge between two of their own instantiations (or
+    /// from one to itself), with the number of such edges, most first.
+    pub fn self_triggering(&self) -> Vec<(String, u64)> {
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for (src, tgts) in &self.edges {
+            let name = self.names[src].as_str();
+            let n = tgts.iter().filter(|t| self.names[*t] == name).count() as u64;
+            if n > 0 {
+                *counts.entry(name).or_default() += n;
+            }
+        }
+        let mut counts: Vec<(String, u64)> = counts
+            .into_iter()
+            .map(|(name, n)| (name.to_owned(), n))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// Incoming plus outgoing edges of every node.
+    pub fn degree_centrality(&self) -> HashMap<(u64, usize), f64> {
+        let reverse = self.reverse_edges();
+        self.nodes
+            .iter()
+            .map(|k| {
+                let out = self.edges.get(k).map_or(0, HashSet::len);
+                let ins = reverse.get(k).map_or(0, HashSet::len);
+                (*k, (out + ins) as f64)
+            })
+            .collect()
+    }
+
+    /// For every node, the number of shortest paths between two other nodes
+    /// that go through it, a path shared by several shortest ones counting
+    /// fractionally (Brandes' algorithm, unnormalized). Takes time
+    /// proportional to nodes times edges.
+    pub fn betweenness_centrality(&self) -> HashMap<(u64, usize), f64> {
+        let mut nodes: Vec<(u64, usize)> = self.nodes.iter().copied().collect();
+        nodes.sort();
+        let index: HashMap<(u64, usize), usize> =
+            nodes.iter().enumerate().map(|(i, k)| (*k, i)).collect();
+        let succs: Vec<Vec<usize>> = nodes
+            .iter()
+            .map(|k| self.successors(*k).map(|s| index[&s]).collect())
+            .collect();
+        let n = nodes.len();
+        let mut score = vec![0.; n];
+        for source in 0..n {
+            // Shortest paths from `source`, in the order they were found
+            let mut order = Vec::new();
+            let mut preds: Vec<Vec<usize>> = vec![Vec::new(); n];
+            let mut paths = vec![0.; n];
+            let mut dist: Vec<Option<usize>> = vec![None; n];
+            paths[source] = 1.;
+            dist[source] = Some(0);
+            let mut queue = VecDeque::from([source]);
+            while let Some(v) = queue.pop_front() {
+                order.push(v);
+                let d = dist[v].unwrap_or(0) + 1;
+                for &w in &succs[v] {
+                    if dist[w].is_none() {
+                        dist[w] = Some(d);
+                        queue.push_back(w);
+                    }
+                    if dist[w] == Some(d) {
+                        paths[w] += paths[v];
+                        preds[w].push(v);
+                    }
+                }
+            }
+            let mut dependency = vec![0.; n];
+            for &w in order.iter().rev() {
+                for &v in &preds[w] {
+                    dependency[v] += paths[v] / paths[w] * (1. + dependency[w]);
+                }
+                if w != source {
+                    score[w] += dependency[w];
+                }
+            }
+        }
+        nodes.into_iter().zip(score).collect()
+    }
+
+    /// Fewest edges from a node without incoming edges to every node that can
+    /// be reached from one. Nodes that are only reachable through a cycle
+    /// nothing leads into are missing.
+    pub fn depths(&self) -> HashMap<(u64, usize), usize> {
+        let targets: HashSet<&(u64, usize)> = self.edges.values().flatten().collect();
+        let mut depths: HashMap<(u64, usize), usize> = self
+            .nodes
+            .iter()
+            .filter(|k| !targets.contains(k))
+            .map(|k| (*k, 0))
+            .collect();
+        let mut queue: VecDeque<(u64, usize)> = depths.keys().copied().collect();
+        while let Some(node) = queue.pop_front() {
+            let depth = depths[&node];
+            for next in self.edges.get(&node).into_iter().flatten() {
+                if !depths.contains_key(next) {
+                    depths.insert(*next, depth + 1);
+                    queue.push_back(*next);
+                }
+            }
+        }
+        depths
+    }
+
+    /// Column and row of every node in a layered drawing: one row per
+    /// `InstantiationGraph::depths` level, roots at the top, in key order
+    /// within a row. Nodes only reachable through a cycle go in one more row
+    /// at the bottom.
+    pub fn layers(&self) -> HashMap<(u64, usize), (usize, usize)> {
+        let depths = self.depths();
+        let bottom = depths.values().max().map_or(0, |d| d + 1);
+        let mut nodes: Vec<(usize, (u64, usize))> = self
+            .nodes
+            .iter()
+            .map(|k| (depths.get(k).copied().unwrap_or(bottom), *k))
+            .collect();
+        nodes.sort();
+        let mut row_len: HashMap<usize, usize> = HashMap::new();
+        nodes
+            .into_iter()
+            .map(|(row, k)| {
+                let column = row_len.entry(row).or_default();
+                *column += 1;
+                (k, (*column - 1, row))
+            })
+            .collect()
+    }
+
+    /// A longest path, from its root on, through the condensation: a cycle
+    /// counts once rather than forever, and the result has one node per
+    /// component, the one the path enters it at (for the first component,
+    /// the one it leaves from). Within a cycle the path may leave from
+    /// another member, so consecutive nodes are not always adjacent. Of
+    /// several longest paths, the one first in topological order. Empty for
+    /// an empty graph.
+    pub fn longest_chain(&self) -> Vec<(u64, usize)> {
+        let Condensation {
+            components,
+            component,
+            successors,
+        } = self.condensation();
+        // Components come after all of their predecessors
+        let mut depth = vec![1; components.len()];
+        let mut parent: Vec<Option<usize>> = vec![None; components.len()];
+        for (i, succs) in successors.iter().enumerate() {
+            for s in succs {
+                if depth[i] + 1 > depth[*s] {
+                    depth[*s] = depth[i] + 1;
+                    parent[*s] = Some(i);
+                }
+            }
+        }
+        let Some(mut last) = (0..components.len()).rev().max_by_key(|i| depth[*i]) else {
+            return Vec::new();
+        };
+        let mut path = vec![last];
+        while let Some(prev) = parent[last] {
+            path.push(prev);
+            last = prev;
+        }
+        path.reverse();
+
+        // The first edge in key order between each two consecutive components
+        let mut steps: HashMap<(usize, usize), Edge> = HashMap::new();
+        for (src, tgt) in self.sorted_edges() {
+            steps
+                .entry((component[&src], component[&tgt]))
+                .or_insert((src, tgt));
+        }
+        let mut chain: Vec<(u64, usize)> = Vec::with_capacity(path.len());
+        for (i, c) in path.iter().enumerate() {
+            let node = match (i.checked_sub(1), path.get(i + 1)) {
+                (Some(prev), _) => steps[&(path[prev], *c)].1,
+                (None, Some(next)) => steps[&(*c, *next)].0,
+                (None, None) => components[*c][0],
+            };
+            chain.push(node);
+        }
+        chain
+    }
+
+    /// For every quantifier, the smallest and largest version among its
+    /// instantiations, i.e. how long it stayed active.
+    pub fn lifespans(&self) -> HashMap<String, (usize, usize)> {
+        let mut spans: HashMap<String, (usize, usize)> = HashMap::new();
+        for (key, name) in &self.names {
+            let span = spans.entry(name.clone()).or_insert((key.1, key.1));
+            *span = (span.0.min(key.1), span.1.max(key.1));
+        }
+        spans
+    }
+
+    /// For every quantifier, how many other nodes are reachable from its
+    /// instantiations.
+    pub fn descendants_by_quantifier(&self) -> HashMap<String, usize> {
+        let mut by_name: HashMap<&str, Vec<(u64, usize)>> = HashMap::new();
+        for (k, name) in &self.names {
+            by_name.entry(name).or_default().push(*k);
+        }
+        by_name
+            .into_iter()
+            .map(|(name, starts)| {
+                let mut seen: HashSet<(u64, usize)> = starts.iter().copied().collect();
+                let own = seen.len();
+                let mut stack = starts;
+                while let Some(node) = stack.pop() {
+                    for next in self.edges.get(&node).into_iter().flatten() {
+                        if seen.insert(*next) {
+                            stack.push(*next);
+                        }
+                    }
+                }
+                (name.to_owned(), seen.len() - own)
+            })
+            .collect()
+    }
+
+    /// One node per quantifier name.
+    pub fn by_quantifier(&self) -> InstantiationGraph {
+        self.collapse(|_, name| (name.to_owned(), 0))
+    }
+
+    /// Remove every edge between strongly-connected components that is implied
+    /// by a longer path. Edges inside a component are kept, so reachability is
+    /// unchanged.
+    pub fn transitive_reduction(&self) -> InstantiationGraph {
+        let sccs = self.sccs();
+        let mut component = HashMap::new();
+        for (i, scc) in sccs.iter().enumerate() {
+            for node in scc {
+                component.insert(*node, i);
+            }
+        }
+        let mut succs: Vec<BTreeSet<usize>> = vec![BTreeSet::new(); sccs.len()];
+        for (src, tgts) in &self.edges {
+            for tgt in tgts {
+                let (a, b) = (component[src], component[tgt]);
+                if a != b {
+                    succs[a].insert(b);
+                }
+            }
+        }
+
+        // Successors always have smaller indices, so their reachable sets are
+        // complete by the time we get to component `i`.
+        let mut reach: Vec<HashSet<usize>> = Vec::with_capacity(sccs.len());
+        let mut direct: Vec<HashSet<usize>> = Vec::with_capacity(sccs.len());
+        for succ in &succs {
+            let mut indirect = HashSet::new();
+            for s in succ {
+                indirect.extend(reach[*s].iter().copied());
+            }
+            direct.push(
+                succ.iter()
+                    .filter(|s| !indirect.contains(*s))
+                    .copied()
+                    .collect(),
+            );
+            indirect.extend(succ.iter().copied());
+            reach.push(indirect);
+        }
+
+        let mut reduced = self.clone();
+        reduced.edges_changed();
+        for (src, tgts) in reduced.edges.iter_mut() {
+            let a = component[src];
+            tgts.retain(|tgt| {
+                let b = component[tgt];
+                a == b || direct[a].contains(&b)
+            });
+        }
+        reduced.edges.retain(|_, tgts| !tgts.is_empty());
+        let edges = &reduced.edges;
+        reduced
+            .weights
+            .retain(|(src, tgt), _| edges.get(src).is_some_and(|tgts| tgts.contains(tgt)));
+        reduced
+    }
+}
+
+/// A node of the trigger graph: either a quantifier instantiation or the
+/// trigger pattern through which an instantiation was matched.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TriggerNode {
+    Instantiation((u64, usize)),
+    Trigger(Ident),
+}
+
+impl TriggerNode {
+    /// The identifier of the node in the DOT and graph JSON exports: `node_id`
+    /// for instantiations and `p` followed by the term id for triggers, e.g.
+    /// `p#3`.
+    pub fn id(&self) -> String {
+        match self {
+            TriggerNode::Instantiation(key) => node_id(*key),
+            TriggerNode::Trigger(ident) => format!("p{}", ident),
+        }
+    }
+}
+
+/// Like `InstantiationGraph`, but every edge U->V is split into U->P->V,
+/// where P is the trigger of V that matched a term produced by U.
+#[derive(Debug, Default)]
+pub struct TriggerGraph {
+    pub edges: HashMap<TriggerNode, HashSet<TriggerNode>>,
+    pub names: HashMap<TriggerNode, String>,
+    pub nodes: HashSet<TriggerNode>,
+}
+
+impl TriggerGraph {
+    fn add_edge(&mut self, src: TriggerNode, dst: TriggerNode) {
+        self.nodes.insert(src.clone());
+        self.nodes.insert(dst.clone());
+        self.edges.entry(src).or_default().insert(dst);
+    }
+
+    /// Instantiations first, in key order, then triggers by term id.
+    pub fn sorted_nodes(&self) -> Vec<&TriggerNode> {
+        let mut nodes: Vec<&TriggerNode> = self.nodes.iter().collect();
+        nodes.sort();
+        nodes
+    }
+
+    /// The graph as plain nodes and edges, both in order.
+    pub fn export(&self) -> TriggerGraphExport {
+        TriggerGraphExport {
+            nodes: self
+                .sorted_nodes()
+                .into_iter()
+                .map(|node| TriggerExportNode {
+                    id: node.id(),
+                    name: self.names[node].clone(),
+                    kind: match node {
+                        TriggerNode::Instantiation(_) => "instantiation",
+                        TriggerNode::Trigger(_) => "trigger",
+                    },
+                })
+                .collect(),
+            edges: self
+                .sorted_edges()
+                .into_iter()
+                .map(|(src, tgt)| TriggerExportEdge {
+                    source: src.id(),
+                    target: tgt.id(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Write `TriggerGraph::export` as JSON.
+    pub fn write_json(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &self.export())?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Sorted by source, then target, see `TriggerGraph::sorted_nodes`.
+    pub fn sorted_edges(&self) -> Vec<(&TriggerNode, &TriggerNode)> {
+        let mut edges: Vec<(&TriggerNode, &TriggerNode)> = self
+            .edges
+            .iter()
+            .flat_map(|(src, dsts)| dsts.iter().map(move |dst| (src, dst)))
+            .collect();
+        edges.sort();
+        edges
+    }
+}
+
+/// A term that took part in a pattern match, identified by its trace id.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RawMatchedTerm {
+    Trigger(String),
+    Equality(String, String),
+}
+
+/// One `NewMatch` instantiation, before it is folded into the graph.
+#[derive(Debug, Clone, Serialize)]
+pub struct RawMatch {
+    pub qi_key: (u64, usize),
+    pub quantifier: String,
+    pub used: Vec<RawMatchedTerm>,
+}
+
+/// How often a single ground term was matched by some trigger.
+#[derive(Debug, Clone)]
+pub struct TriggerTermCount {
+    pub term: String,
+    pub matches: u64,
+    /// Number of distinct quantifiers the term was matched for
+    pub quantifiers: usize,
+    /// The matches of each of those quantifiers
+    pub per_quantifier: BTreeMap<String, u64>,
+}
+
+/// Most matches first, then by term.
+fn sort_trigger_terms(terms: &mut [TriggerTermCount]) {
+    terms.sort_by(|a, b| b.matches.cmp(&a.matches).then_with(|| a.term.cmp(&b.term)));
+}
+
+/// A term behind an edge of the instantiation graph: the source produced it
+/// and the target matched on it, directly or through an equality.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EdgeTerm {
+    pub id: String,
+    /// Pretty-printed, or the id again if that fails
+    pub term: String,
+}
+
+/// A merge of one term into another's equivalence class, as logged by an
+/// `[eq-expl]` line, see `Profiler::equality_merges`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EqualityMerge {
+    pub term: EdgeTerm,
+    pub into: EdgeTerm,
+    /// Why Z3 merged them, e.g. `lit #34` for a literal or `cg (#1 #2)` for
+    /// congruence
+    pub reason: String,
+}
+
+/// A cycle of the instantiation graph, see `Profiler::matching_loops`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchingLoop {
+    /// All instantiations of the strongly-connected component
+    pub instantiations: Vec<(u64, usize)>,
+    /// Quantifier names along one shortest cycle of the component, starting
+    /// from its smallest instantiation
+    pub cycle: Vec<String>,
+}
+
+/// How one quantifier's instantiations are spread over
+/// `InstantiationGraph::depths`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepthHistogram {
+    pub quantifier: String,
+    /// Instantiations at each depth, from 0 up to the deepest one
+    pub counts: Vec<u64>,
+    /// Instantiations only reachable through a cycle nothing leads into,
+    /// which have no depth
+    pub unreachable: u64,
+}
+
+/// How many instantiations of one quantifier came from `QiFrame::Discovered`
+/// frames (theory reasoning or MBQI) rather than from E-matching.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredCount {
+    pub quantifier: String,
+    pub instantiations: u64,
+}
+
+/// Most instantiations first, then by name.
+fn sort_discovered(discovered: &mut [DiscoveredCount]) {
+    discovered.sort_by(|a, b| {
+        b.instantiations
+            .cmp(&a.instantiations)
+            .then_with(|| a.quantifier.cmp(&b.quantifier))
+    });
+}
+
+/// How many matches of one quantifier were on terms no instantiation
+/// produced, i.e. on the input problem rather than on a cascade.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrphanCount {
+    pub quantifier: String,
+    pub matches: u64,
+    /// Matches none of whose trigger terms has a blamed producer
+    pub orphan_matches: u64,
+    /// Matched trigger terms without a blamed producer, over all matches
+    pub orphan_terms: u64,
+}
+
+/// One entry of `StatsReport`.
+#[derive(Debug, Clone, Serialize)]
+pub struct QuantifierStats {
+    pub quantifier: String,
+    pub instantiations: u64,
+    pub cost: u64,
+    /// See `Profiler::enodes`
+    pub enodes: u64,
+    /// Share of all instantiations, in percent
+    pub percentage: f64,
+    /// Edges into instantiations of this quantifier
+    pub in_degree: u64,
+    /// Edges out of instantiations of this quantifier
+    pub out_degree: u64,
+}
+
+/// The quantifier statistics in the order of `Profiler::quantifier_stats`,
+/// for `--json`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatsReport {
+    pub total_instantiations: u64,
+    pub nodes: usize,
+    pub edges: usize,
+    pub quantifiers: Vec<QuantifierStats>,
+}
+
+/// The instantiation graph as written by `--graph-json`, for web
+/// visualizers.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphExport {
+    pub nodes: Vec<ExportNode>,
+    pub edges: Vec<ExportEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportNode {
+    /// See `node_id`
+    pub id: String,
+    pub name: String,
+    /// Of the node's quantifier
+    pub instantiations: u64,
+    /// Produced by the node's quantifier, see `Profiler::enodes`
+    pub enodes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExportEdge {
+    pub source: String,
+    pub target: String,
+    pub weight: u64,
+}
+
+/// The trigger graph as written by `--graph-json` with `--trigger-graph`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerGraphExport {
+    pub nodes: Vec<TriggerExportNode>,
+    pub edges: Vec<TriggerExportEdge>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerExportNode {
+    /// See `TriggerNode::id`
+    pub id: String,
+    pub name: String,
+    /// `instantiation` or `trigger`
+    pub kind: &'static str,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TriggerExportEdge {
+    pub source: String,
+    pub target: String,
+}
+
+/// Which instantiation produced each enode, as used to build the graph.
+#[derive(Debug, Default, Clone)]
+pub struct TermBlame {
+    pub producers: HashMap<Ident, QiKey>,
+    /// Quantifier name of every instantiation in `producers`
+    pub quantifiers: HashMap<QiKey, String>,
+}
+
+impl TermBlame {
+    fn new(names: &HashMap<QiKey, &str>, blame: &HashMap<&Ident, &QiKey>) -> Self {
+        let producers: HashMap<Ident, QiKey> = blame
+            .iter()
+            .filter(|(_, qi_key)| names.contains_key(qi_key))
+            .map(|(ident, qi_key)| ((*ident).clone(), **qi_key))
+            .collect();
+        let quantifiers = producers
+            .values()
+            .map(|qi_key| (*qi_key, names[qi_key].to_owned()))
+            .collect();
+        TermBlame {
+            producers,
+            quantifiers,
+        }
+    }
+}
+
+/// Instantiation counts of one quantifier in two runs, see `Profiler::compare`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuantifierDelta {
+    pub quant: String,
+    pub before: u64,
+    pub after: u64,
+}
+
+impl QuantifierDelta {
+    pub fn delta(&self) -> i64 {
+        self.after as i64 - self.before as i64
+    }
+
+    /// Change relative to `before`, in percent. A quantifier that only
+    /// appears in the second run counts as a 100% change.
+    pub fn delta_pct(&self) -> f64 {
+        if self.before == 0 {
+            if self.after == 0 {
+                0.0
+            } else {
+                100.0
+            }
+        } else {
+            100.0 * self.delta().unsigned_abs() as f64 / self.before as f64
+        }
+    }
+}
+
+/// Minimum change for a `QuantifierDelta` to be worth reporting; both
+/// bounds have to be met.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DiffThreshold {
+    pub min_delta: u64,
+    pub min_delta_pct: f64,
+}
+
+impl DiffThreshold {
+    pub fn passes(&self, delta: &QuantifierDelta) -> bool {
+        delta.delta().unsigned_abs() >= self.min_delta && delta.delta_pct() >= self.min_delta_pct
+    }
+}
+
+/// `QuantCost` as stored by `Profiler::save_bin`; z3tracer's type is not
+/// serializable.
+#[derive(Serialize, Deserialize)]
+struct QuantCostRecord {
+    quant: String,
+    instantiations: u64,
+    cost: u64,
+}
+
+/// How many traces `Profiler::merge` can combine: one per value of the top
+/// byte of the instantiation keys.
+pub const MAX_MERGED_TRACES: usize = 256;
+
+/// Start of every file written by `Profiler::save_bin`, followed by
+/// `PROFILE_FORMAT_VERSION` as a little-endian `u32` and `PROFILE_BUILD`.
+const PROFILE_MAGIC: &[u8; 8] = b"GORDIAN\0";
+/// Bump whenever the serialized layout of the profile changes.
+const PROFILE_FORMAT_VERSION: u32 = 5;
+/// The build that wrote a profile. Profiles are only read back by the same
+/// release, in case a layout change went without a format version bump.
+const PROFILE_BUILD: &str = concat!(env!("CARGO_PKG_NAME"), " ", env!("CARGO_PKG_VERSION"));
+
+/// What `Profiler::save_bin` writes after the version.
+type ProfileRecord = (
+    Vec<QuantCostRecord>,
+    InstantiationGraph,
+    HashMap<String, u64>,
+);
+
+#[derive(Debug, Default, Clone)]
+pub struct ProfilerOptions {
+    /// Add `QiFrame::Discovered` instantiations to the graph, with edges from
+    /// the producers of the terms blamed for them
+    pub include_discovered: bool,
+    pub trigger_graph: bool,
+    pub raw_matches: bool,
+    pub by_trigger: bool,
+    /// Record the terms behind every edge in `Profiler::edge_terms`
+    pub edge_terms: bool,
+    pub orphan_report: bool,
+    /// Run z3tracer's log consistency checks, collecting problems instead of aborting
+    pub consistency_checks: bool,
+    /// Z3 version to assume for traces without a `[tool-version]` line
+    pub z3_version: Option<String>,
+    /// Have z3tracer reject traces from Z3 versions it does not know
+    pub check_z3_version: bool,
+    /// Fail on the first trace line z3tracer cannot handle instead of
+    /// skipping it and counting it in `Profiler::ignored_lines`
+    pub strict_lines: bool,
+    /// Have z3tracer log the term equalities it derives, which
+    /// `ProfilerOptions::why_not` needs. The equality edges of the graph come
+    /// from the matches themselves and do not need this.
+    pub log_equalities: bool,
+    /// Quantifiers expected to fire, whose trigger terms' equality merges
+    /// to collect in `Profiler::equality_merges`; needs `log_equalities`
+    pub why_not: Vec<String>,
+    /// Skip instantiations whose quantifier cannot be named, with a warning,
+    /// instead of failing
+    pub lenient: bool,
+    /// Threads to build the instantiation graph with; rayon's default of one
+    /// per core if `None`
+    pub threads: Option<usize>,
+    /// Report progress and phase times on stderr, for traces that take long
+    pub verbose: bool,
+    /// Hide z3tracer's progress bar
+    pub quiet: bool,
+}
+
+#[derive(Debug)]
+pub struct Profiler {
+    /// Most expensive first, by `default_cost` unless re-ranked with
+    /// `Profiler::rank_by`
+    pub quantifier_stats: Vec<QuantCost>,
+    pub instantiation_graph: InstantiationGraph,
+    /// Only built with `ProfilerOptions::trigger_graph`
+    pub trigger_graph: Option<TriggerGraph>,
+    /// Only collected with `ProfilerOptions::raw_matches`
+    pub raw_matches: Vec<RawMatch>,
+    /// Only collected with `ProfilerOptions::by_trigger`
+    pub trigger_terms: Vec<TriggerTermCount>,
+    /// Only collected with `ProfilerOptions::edge_terms`; several terms can
+    /// be behind one edge
+    pub edge_terms: BTreeMap<Edge, Vec<EdgeTerm>>,
+    /// Only collected with `ProfilerOptions::why_not`: for each of those
+    /// quantifiers, the merges of terms headed by a function symbol of its
+    /// triggers, in trace order
+    pub equality_merges: BTreeMap<String, Vec<EqualityMerge>>,
+    /// Quantifiers without a name, which go by `<anon:ID>` instead; 0 for
+    /// profiles loaded with `Profiler::load_bin`
+    pub anonymous_quantifiers: usize,
+    pub term_blame: TermBlame,
+    /// Only collected with `ProfilerOptions::orphan_report`
+    pub orphans: Vec<OrphanCount>,
+    /// Most first; empty for profiles loaded with `Profiler::load_bin`
+    pub discovered: Vec<DiscoveredCount>,
+    /// Enodes the instantiations of each quantifier added to the E-graph
+    pub enodes: HashMap<String, u64>,
+    /// First and last instantiation version of every quantifier in the graph
+    pub lifespans: HashMap<String, (usize, usize)>,
+    /// The original names behind each quantifier renamed by
+    /// `Profiler::rename_quantifiers`
+    pub raw_names: BTreeMap<String, BTreeSet<String>>,
+    pub consistency: ConsistencyReport,
+    /// Trace lines z3tracer could not handle and skipped; if non-zero the
+    /// profile is incomplete
+    pub ignored_lines: usize,
+    /// Why the trace looks cut off, e.g. by a killed or timed-out Z3, in
+    /// which case the graph may be missing its last instantiations
+    pub truncation: Option<String>,
+    pub timings: Timings,
+}
+
+impl Profiler {
+    pub fn parse(filename: &std::path::Path) -> anyhow::Result<Self> {
+        Self::parse_with_options(filename, &ProfilerOptions::default())
+    }
+
+    pub fn parse_with_options(
+        filename: &std::path::Path,
+        options: &ProfilerOptions,
+    ) -> anyhow::Result<Self> {
+        check_why_not(options)?;
+        let mut timings = Timings::default();
+        let mut consistency = ConsistencyReport::default();
+        let mut problems = ParseProblems::default();
+        let model = process_file(
+            filename,
+            options,
+            &mut timings,
+            &mut consistency,
+            &mut problems,
+        )?;
+        let mut profiler = Self::from_model(&model, options, timings, consistency)?;
+        profiler.ignored_lines = problems.ignored_lines;
+        profiler.truncation = problems.truncation;
+        profiler.equality_merges =
+            Self::equality_merges(&model, &options.why_not, &problems.equalities)?;
+        Ok(profiler)
+    }
+
+    /// Parse a trace that is already in memory or comes from a stream. The
+    /// line count is only used for the progress bar, which is hidden if it
+    /// is not known.
+    pub fn parse_reader(
+        reader: impl BufRead,
+        line_count_hint: Option<usize>,
+    ) -> anyhow::Result<Self> {
+        Self::parse_reader_with_options(reader, line_count_hint, &ProfilerOptions::default())
+    }
+
+    pub fn parse_reader_with_options(
+        reader: impl BufRead,
+        line_count_hint: Option<usize>,
+        options: &ProfilerOptions,
+    ) -> anyhow::Result<Self> {
+        check_why_not(options)?;
+        let mut timings = Timings::default();
+        let mut consistency = ConsistencyReport::default();
+        let mut problems = ParseProblems::default();
+        let model = process_reader(
+            reader,
+            None,
+            line_count_hint,
+            options,
+            &mut timings,
+            &mut consistency,
+            &mut problems,
+        )?;
+        let mut profiler = Self::from_model(&model, options, timings, consistency)?;
+        profiler.ignored_lines = problems.ignored_lines;
+        profiler.truncation = problems.truncation;
+        profiler.equality_merges =
+            Self::equality_merges(&model, &options.why_not, &problems.equalities)?;
+        Ok(profiler)
+    }
+
+    fn from_model(
+        model: &Model,
+        options: &ProfilerOptions,
+        mut timings: Timings,
+        consistency: ConsistencyReport,
+    ) -> anyhow::Result<Self> {
+        let start = Instant::now();
+        let anonymous = Self::anonymous_labels(model);
+        let names = Self::quantifier_names(model, &anonymous, options.lenient)?;
+        let pool = options
+            .threads
+            .map(|n| rayon::ThreadPoolBuilder::new().num_threads(n).build())
+            .transpose()?;
+        let build_graph = || -> anyhow::Result<_> {
+            let blame = Self::term_blame(model, options.include_discovered);
+            if options.verbose {
+                eprintln!(
+                    "Found the producers of {} terms in {:.1}s",
+                    blame.len(),
+                    start.elapsed().as_secs_f64()
+                );
+            }
+            let graph = Self::make_instantiation_graph(model, &names, &blame, options)?;
+            if options.verbose {
+                eprintln!(
+                    "Built the instantiation graph ({} nodes, {} edges) in {:.1}s",
+                    graph.nodes.len(),
+                    graph.edge_count(),
+                    start.elapsed().as_secs_f64()
+                );
+            }
+            Ok((blame, graph))
+        };
+        let (blame, graph) = match &pool {
+            Some(pool) => pool.install(build_graph)?,
+            None => build_graph()?,
+        };
+        let lifespans = graph.lifespans();
+        let discovered = Self::discovered_counts(model, &names);
+        let enodes = Self::enode_counts(model, &names);
+        let orphans = if options.orphan_report {
+            Self::orphan_counts(model, &names, &blame)
+        } else {
+            Vec::new()
+        };
+        let term_blame = TermBlame::new(&names, &blame);
+        let trigger_graph = if options.trigger_graph {
+            Some(Self::make_trigger_graph(model, &names))
+        } else {
+            None
+        };
+        let raw_matches = if options.raw_matches {
+            Self::raw_matches(model, &names)
+        } else {
+            Vec::new()
+        };
+        let edge_terms = if options.edge_terms {
+            Self::edge_terms(model, &names, &blame, options.include_discovered)
+        } else {
+            BTreeMap::new()
+        };
+        let trigger_terms = if options.by_trigger {
+            Self::trigger_term_counts(model, &names)
+        } else {
+            Vec::new()
+        };
+        timings.graph = start.elapsed();
+
+        let start = Instant::now();
+        let quant_costs = model.quant_costs();
+        timings.stats = start.elapsed();
+
+        let mut profiler = Profiler {
+            quantifier_stats: quant_costs,
+            instantiation_graph: graph,
+            trigger_graph,
+            raw_matches,
+            trigger_terms,
+            edge_terms,
+            equality_merges: BTreeMap::new(),
+            anonymous_quantifiers: anonymous.len(),
+            term_blame,
+            orphans,
+            discovered,
+            enodes,
+            lifespans,
+            raw_names: BTreeMap::new(),
+            consistency,
+            ignored_lines: 0,
+            truncation: None,
+            timings,
+        };
+        let start = Instant::now();
+        profiler.rank_by(default_cost);
+        profiler.timings.stats += start.elapsed();
+        Ok(profiler)
+    }
+
+    /// The merges in `equalities` (`[eq-expl]` lines) of terms headed by a
+    /// function symbol of the triggers of each quantifier in `why_not`. A
+    /// term is looked up by the id on the line, so one whose id Z3 reused is
+    /// taken for the term last given that id.
+    fn equality_merges(
+        model: &Model,
+        why_not: &[String],
+        equalities: &[String],
+    ) -> anyhow::Result<BTreeMap<String, Vec<EqualityMerge>>> {
+        let mut merges = BTreeMap::new();
+        for quantifier in why_not {
+            let mut heads: HashSet<&str> = HashSet::new();
+            let mut found = false;
+            for term in model.terms().values() {
+                let Term::Quant { name, triggers, .. } = term else {
+                    continue;
+                };
+                if name != quantifier {
+                    continue;
+                }
+                found = true;
+                // Each trigger is a `pattern` application over the terms of
+                // one multi-pattern
+                for trigger in triggers {
+                    let Ok(Term::App { args, .. }) = model.term(trigger) else {
+                        continue;
+                    };
+                    for arg in args {
+                        if let Ok(Term::App { name, .. }) = model.term(arg) {
+                            heads.insert(name.as_str());
+                        }
+                    }
+                }
+            }
+            if !found {
+                anyhow::bail!("No quantifier named `{}` in the trace", quantifier);
+            }
+            let headed: HashMap<String, &Ident> = model
+                .terms()
+                .iter()
+                .filter(|(_, term)| {
+                    matches!(term, Term::App { name, .. } if heads.contains(name.as_str()))
+                })
+                .map(|(ident, _)| (ident.to_string(), ident))
+                .collect();
+            let label = |id: &str| EdgeTerm {
+                id: id.to_owned(),
+                term: headed
+                    .get(id)
+                    .map_or_else(|| id.to_owned(), |ident| term_label(model, ident)),
+            };
+            let mut involved = Vec::new();
+            for line in equalities {
+                // `[eq-expl] #12 lit #34 ; #56`; roots are merged into nothing
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let Some(semicolon) = words.iter().position(|w| *w == ";") else {
+                    continue;
+                };
+                let (Some(term), Some(into)) = (words.get(1), words.get(semicolon + 1)) else {
+                    continue;
+                };
+                if headed.contains_key(*term) || headed.contains_key(*into) {
+                    involved.push(EqualityMerge {
+                        term: label(term),
+                        into: label(into),
+                        reason: words[2.min(semicolon)..semicolon].join(" "),
+                    });
+                }
+            }
+            merges.insert(quantifier.clone(), involved);
+        }
+        Ok(merges)
+    }
+
+    /// Labels for the instantiated quantifiers Z3 logged without a name, or
+    /// as `null` as it does for those without a `:qid`: `<anon:ID>` after
+    /// their term id, so that they stay apart and keep their label across
+    /// runs on the same trace.
+    fn anonymous_labels(model: &Model) -> HashMap<&Ident, String> {
+        model
+            .instantiations()
+            .values()
+            .map(|quant_inst| quant_inst.frame.quantifier())
+            .filter(|ident| {
+                model
+                    .term(ident)
+                    .is_ok_and(|term| matches!(term.name(), None | Some("null")))
+            })
+            .map(|ident| (ident, format!("<anon:{}>", ident)))
+            .collect()
+    }
+
+    /// The quantifier name of every instantiation, or its label in
+    /// `anonymous`. With `lenient`, instantiations whose quantifier is not in
+    /// the trace (typically one cut off mid-write) are left out with a
+    /// warning, and so are missing from every analysis; otherwise they are
+    /// an error.
+    fn quantifier_names<'m>(
+        model: &'m Model,
+        anonymous: &'m HashMap<&Ident, String>,
+        lenient: bool,
+    ) -> anyhow::Result<HashMap<QiKey, &'m str>> {
+        let mut names = HashMap::new();
+        for (qi_key, quant_inst) in model.instantiations() {
+            let ident = quant_inst.frame.quantifier();
+            let name = match model.term(ident) {
+                Ok(_) if anonymous.contains_key(ident) => Ok(anonymous[ident].as_str()),
+                Ok(term) => term.name().ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "quantifier {} of instantiation {:#x}/{} has no name",
+                        ident,
+                        qi_key.key,
+                        qi_key.version
+                    )
+                }),
+                Err(e) => Err(anyhow::anyhow!(
+                    "quantifier {} of instantiation {:#x}/{} is not in the trace: {:?}",
+                    ident,
+                    qi_key.key,
+                    qi_key.version,
+                    e
+                )),
+            };
+            match name {
+                Ok(name) => {
+                    names.insert(*qi_key, name);
+                }
+                Err(e) if lenient => eprintln!("warning: skipping an instantiation: {}", e),
+                Err(e) => {
+                    return Err(e.context("malformed trace (--lenient skips such instantiations)"))
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    fn trigger_term_counts<'m>(
+        model: &'m Model,
+        names: &HashMap<QiKey, &'m str>,
+    ) -> Vec<TriggerTermCount> {
+        let mut counts: HashMap<&Ident, BTreeMap<String, u64>> = HashMap::new();
+        for (qi_key, quant_inst) in Self::new_matches(model) {
+            let (_, used) = Self::new_match(quant_inst);
+            let Some(&name) = names.get(qi_key) else {
+                continue;
+            };
+            for u in used.iter() {
+                if let MatchedTerm::Trigger(t) = u {
+                    *counts
+                        .entry(t)
+                        .or_default()
+                        .entry(name.to_owned())
+                        .or_default() += 1;
+                }
+            }
+        }
+        let mut terms: Vec<TriggerTermCount> = counts
+            .into_iter()
+            .map(|(t, per_quantifier)| TriggerTermCount {
+                term: term_label(model, t),
+                matches: per_quantifier.values().sum(),
+                quantifiers: per_quantifier.len(),
+                per_quantifier,
+            })
+            .collect();
+        sort_trigger_terms(&mut terms);
+        terms
+    }
+
+    fn orphan_counts(
+        model: &Model,
+        names: &HashMap<QiKey, &str>,
+        term_blame: &HashMap<&Ident, &QiKey>,
+    ) -> Vec<OrphanCount> {
+        let mut counts: HashMap<&str, OrphanCount> = HashMap::new();
+        for (qi_key, quant_inst) in Self::new_matches(model) {
+            let (_, used) = Self::new_match(quant_inst);
+            let Some(&name) = names.get(qi_key) else {
+                continue;
+            };
+            let count = counts.entry(name).or_insert_with(|| OrphanCount {
+                quantifier: name.to_owned(),
+                matches: 0,
+                orphan_matches: 0,
+                orphan_terms: 0,
+            });
+            count.matches += 1;
+            let mut blamed = false;
+            for u in used.iter() {
+                if let MatchedTerm::Trigger(t) = u {
+                    if term_blame.contains_key(t) {
+                        blamed = true;
+                    } else {
+                        count.orphan_terms += 1;
+                    }
+                }
+            }
+            if !blamed {
+                count.orphan_matches += 1;
+            }
+        }
+        let mut orphans: Vec<OrphanCount> = counts.into_values().collect();
+        orphans.sort_by(|a, b| {
+            b.orphan_matches
+                .cmp(&a.orphan_matches)
+                .then_with(|| a.quantifier.cmp(&b.quantifier))
+        });
+        orphans
+    }
+
+    fn enode_counts(model: &Model, names: &HashMap<QiKey, &str>) -> HashMap<String, u64> {
+        let mut counts: HashMap<String, u64> = HashMap::new();
+        for (qi_key, quant_inst) in model.instantiations() {
+            let Some(&name) = names.get(qi_key) else {
+                continue;
+            };
+            let produced: usize = quant_inst.instances.iter().map(|i| i.enodes.len()).sum();
+            *counts.entry(name.to_owned()).or_default() += produced as u64;
+        }
+        counts
+    }
+
+    /// Enodes produced by instantiations of `quantifier`, see `Profiler::enodes`.
+    pub fn enodes_of(&self, quantifier: &str) -> u64 {
+        self.enodes.get(quantifier).copied().unwrap_or(0)
+    }
+
+    fn discovered_counts(model: &Model, names: &HashMap<QiKey, &str>) -> Vec<DiscoveredCount> {
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for (qi_key, quant_inst) in model.instantiations() {
+            if let QiFrame::Discovered { .. } = quant_inst.frame {
+                if let Some(&name) = names.get(qi_key) {
+                    *counts.entry(name).or_default() += 1;
+                }
+            }
+        }
+        let mut discovered: Vec<DiscoveredCount> = counts
+            .into_iter()
+            .map(|(name, instantiations)| DiscoveredCount {
+                quantifier: name.to_owned(),
+                instantiations,
+            })
+            .collect();
+        sort_discovered(&mut discovered);
+        discovered
+    }
+
+    fn raw_matches(model: &Model, names: &HashMap<QiKey, &str>) -> Vec<RawMatch> {
+        let mut matches: Vec<RawMatch> = Self::new_matches(model)
+            .filter_map(|(qi_key, quant_inst)| {
+                let (_, used) = Self::new_match(quant_inst);
+                let name = *names.get(qi_key)?;
+                Some(RawMatch {
+                    qi_key: (qi_key.key, qi_key.version),
+                    quantifier: name.to_owned(),
+                    used: used
+                        .iter()
+                        .map(|u| match u {
+                            MatchedTerm::Trigger(t) => RawMatchedTerm::Trigger(t.to_string()),
+                            MatchedTerm::Equality(t1, t2) => {
+                                RawMatchedTerm::Equality(t1.to_string(), t2.to_string())
+                            }
+                        })
+                        .collect(),
+                })
+            })
+            .collect();
+        matches.sort_by_key(|m| m.qi_key);
+        matches
+    }
+
+    pub fn stats_report(&self) -> StatsReport {
+        let graph = &self.instantiation_graph;
+        let total = self.total_instantiations();
+        let degrees = graph.degrees_by_quantifier();
+        StatsReport {
+            total_instantiations: total,
+            nodes: graph.nodes.len(),
+            edges: graph.edge_count(),
+            quantifiers: self
+                .quantifier_stats
+                .iter()
+                .map(|cost| {
+                    let (in_degree, out_degree) = degrees
+                        .get(cost.quant.as_str())
+                        .copied()
+                        .unwrap_or_default();
+                    QuantifierStats {
+                        quantifier: cost.quant.clone(),
+                        instantiations: cost.instantiations,
+                        cost: cost.cost,
+                        enodes: self.enodes_of(&cost.quant),
+                        percentage: percentage(cost.instantiations, total),
+                        in_degree,
+                        out_degree,
+                    }
+                })
+                .collect(),
+        }
+    }
+
+    /// The instantiation graph for `--graph-json`, nodes and edges in key
+    /// order.
+    pub fn graph_export(&self) -> GraphExport {
+        let graph = &self.instantiation_graph;
+        let counts: HashMap<&str, u64> = self
+            .quantifier_stats
+            .iter()
+            .map(|cost| (cost.quant.as_str(), cost.instantiations))
+            .collect();
+        let nodes = graph.sorted_nodes();
+        let edges = graph.sorted_edges();
+        GraphExport {
+            nodes: nodes
+                .iter()
+                .map(|k| ExportNode {
+                    id: node_id(*k),
+                    name: graph.names[k].clone(),
+                    instantiations: counts.get(graph.names[k].as_str()).copied().unwrap_or(0),
+                    enodes: self.enodes_of(&graph.names[k]),
+                })
+                .collect(),
+            edges: edges
+                .into_iter()
+                .map(|(src, tgt)| ExportEdge {
+                    source: node_id(src),
+                    target: node_id(tgt),
+                    weight: graph.weight(src, tgt),
+                })
+                .collect(),
+        }
+    }
+
+    /// Write `Profiler::graph_export` as JSON.
+    pub fn write_graph_json(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer(&mut out, &self.graph_export())?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write `Profiler::stats_report` as JSON.
+    pub fn write_json(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(&mut out, &self.stats_report())?;
+        out.write_all(b"\n")?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write the matches collected with `ProfilerOptions::raw_matches` as JSON lines.
+    pub fn write_raw_matches(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        for m in &self.raw_matches {
+            serde_json::to_writer(&mut out, m)?;
+            out.write_all(b"\n")?;
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Write the quantifier statistics, the instantiation graph and the enode
+    /// counts in a compact binary format, to be read back with `Profiler::load_bin`.
+    pub fn save_bin(&self, path: &std::path::Path) -> anyhow::Result<()> {
+        let stats: Vec<QuantCostRecord> = self
+            .quantifier_stats
+            .iter()
+            .map(|cost| QuantCostRecord {
+                quant: cost.quant.clone(),
+                instantiations: cost.instantiations,
+                cost: cost.cost,
+            })
+            .collect();
+        let mut out = std::io::BufWriter::new(File::create(path)?);
+        out.write_all(PROFILE_MAGIC)?;
+        out.write_all(&PROFILE_FORMAT_VERSION.to_le_bytes())?;
+        bincode::serialize_into(&mut out, PROFILE_BUILD)?;
+        bincode::serialize_into(&mut out, &(&stats, &self.instantiation_graph, &self.enodes))?;
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read a profile written by `Profiler::save_bin`. Only the statistics,
+    /// the instantiation graph and the enode counts are stored, everything
+    /// else is empty.
+    pub fn load_bin(path: &std::path::Path) -> anyhow::Result<Self> {
+        let mut input = std::io::BufReader::new(File::open(path)?);
+        let mut magic = [0; PROFILE_MAGIC.len()];
+        input.read_exact(&mut magic)?;
+        if &magic != PROFILE_MAGIC {
+            anyhow::bail!("{} is not a saved profile", path.display());
+        }
+        let mut version = [0; 4];
+        input.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != PROFILE_FORMAT_VERSION {
+            anyhow::bail!(
+                "{} uses profile format version {}, but this build only reads version {}",
+                path.display(),
+                version,
+                PROFILE_FORMAT_VERSION
+            );
+        }
+        let build: String = bincode::deserialize_from(&mut input)?;
+        if build != PROFILE_BUILD {
+            anyhow::bail!(
+                "{} was written by {}, but this is {}; re-create it from the trace",
+                path.display(),
+                build,
+                PROFILE_BUILD
+            );
+        }
+        let (stats, instantiation_graph, enodes): ProfileRecord = bincode::deserialize_from(input)?;
+        let lifespans = instantiation_graph.lifespans();
+        Ok(Profiler {
+            quantifier_stats: stats
+                .into_iter()
+                .map(|record| QuantCost {
+                    quant: record.quant,
+                    instantiations: record.instantiations,
+                    cost: record.cost,
+                })
+                .collect(),
+            instantiation_graph,
+            trigger_graph: None,
+            raw_matches: Vec::new(),
+            trigger_terms: Vec::new(),
+            edge_terms: BTreeMap::new(),
+            equality_merges: BTreeMap::new(),
+            anonymous_quantifiers: 0,
+            term_blame: TermBlame::default(),
+            orphans: Vec::new(),
+            discovered: Vec::new(),
+            enodes,
+            lifespans,
+            raw_names: BTreeMap::new(),
+            consistency: ConsistencyReport::default(),
+            ignored_lines: 0,
+            truncation: None,
+            timings: Timings::default(),
+        })
+    }
+
+    /// Whether `path` starts like a file written by `Profiler::save_bin`.
+    pub fn is_saved_profile(path: &std::path::Path) -> bool {
+        let mut magic = [0; PROFILE_MAGIC.len()];
+        File::open(path)
+            .and_then(|mut f| f.read_exact(&mut magic))
+            .is_ok()
+            && &magic == PROFILE_MAGIC
+    }
+
+    fn graph_instantiations(
+        model: &Model,
+        include_discovered: bool,
+    ) -> impl Iterator<Item = (&QiKey, &QuantInstantiation)> + Clone {
+        model
+            .instantiations()
+            .iter()
+            .filter(move |(_, quant_inst)| match quant_inst.frame {
+                QiFrame::Discovered { .. } => include_discovered,
+                QiFrame::NewMatch { .. } => true,
+            })
+    }
+
+    fn new_matches(model: &Model) -> impl Iterator<Item = (&QiKey, &QuantInstantiation)> + Clone {
+        Self::graph_instantiations(model, false)
+    }
+
+    /// The trigger and matched terms of an instantiation from `new_matches`.
+    fn new_match(quant_inst: &QuantInstantiation) -> (&Ident, &[MatchedTerm]) {
+        match &quant_inst.frame {
+            QiFrame::Discovered { .. } => {
+                panic!("We filtered out all of the Discovered instances already!")
+            }
+            QiFrame::NewMatch { trigger, used, .. } => (trigger, used),
+        }
+    }
+
+    // Track which instantiations caused which enodes to appear
+    fn term_blame(model: &Model, include_discovered: bool) -> HashMap<&Ident, &QiKey> {
+        // An enode claimed twice goes to the earliest instantiation, so the
+        // result does not depend on how the work was split between threads
+        fn claim<'a>(blame: &mut HashMap<&'a Ident, &'a QiKey>, t: &'a Ident, qi_key: &'a QiKey) {
+            blame
+                .entry(t)
+                .and_modify(|owner| *owner = (*owner).min(qi_key))
+                .or_insert(qi_key);
+        }
+        let instantiations: Vec<_> =
+            Self::graph_instantiations(model, include_discovered).collect();
+        instantiations
+            .par_iter()
+            .fold(HashMap::new, |mut blame, (qi_key, quant_inst)| {
+                for inst in &quant_inst.instances {
+                    for node_ident in &inst.enodes {
+                        claim(&mut blame, node_ident, qi_key);
+                    }
+                }
+                blame
+            })
+            .reduce(HashMap::new, |mut blame, partial| {
+                for (t, qi_key) in partial {
+                    claim(&mut blame, t, qi_key);
+                }
+                blame
+            })
+    }
+
+    fn make_trigger_graph(model: &Model, names: &HashMap<QiKey, &str>) -> TriggerGraph {
+        let term_blame = Self::term_blame(model, false);
+        let mut graph = TriggerGraph::default();
+        for (qi_key, quant_inst) in Self::new_matches(model) {
+            let (trigger, used) = Self::new_match(quant_inst);
+            let Some(&name) = names.get(qi_key) else {
+                continue;
+            };
+
+            // Trigger nodes are shared by all instantiations matched through them
+            let inst_node = TriggerNode::Instantiation((qi_key.key, qi_key.version));
+            let trigger_node = TriggerNode::Trigger(trigger.clone());
+            let pattern = term_label(model, trigger);
+            graph.names.insert(inst_node.clone(), name.to_owned());
+            graph
+                .names
+                .insert(trigger_node.clone(), format!("{} {}", name, pattern));
+            graph.add_edge(trigger_node.clone(), inst_node);
+
+            for used in used.iter() {
+                if let MatchedTerm::Trigger(t) = used {
+                    if let Some(qi_responsible) = term_blame.get(&t) {
+                        let src = TriggerNode::Instantiation((
+                            qi_responsible.key,
+                            qi_responsible.version,
+                        ));
+                        graph.add_edge(src, trigger_node.clone());
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    /// The terms whose producers are to blame for `quant_inst`.
+    fn matched_terms(quant_inst: &QuantInstantiation) -> Vec<&Ident> {
+        match &quant_inst.frame {
+            // No trigger was matched, so blame whoever produced the
+            // terms that justified the instantiation
+            QiFrame::Discovered { blame, .. } => blame.iter().collect(),
+            QiFrame::NewMatch { used, .. } => used
+                .iter()
+                .flat_map(|u| match u {
+                    // Quantifier instantiation that produced the triggering term
+                    MatchedTerm::Trigger(t) => vec![t],
+                    // The match went through `t1 = t2`, so whoever
+                    // produced either side made it possible
+                    MatchedTerm::Equality(t1, t2) => vec![t1, t2],
+                })
+                .collect(),
+        }
+    }
+
+    /// The terms behind each edge `make_instantiation_graph` creates.
+    fn edge_terms(
+        model: &Model,
+        names: &HashMap<QiKey, &str>,
+        term_blame: &HashMap<&Ident, &QiKey>,
+        include_discovered: bool,
+    ) -> BTreeMap<Edge, Vec<EdgeTerm>> {
+        let mut terms: BTreeMap<Edge, Vec<EdgeTerm>> = BTreeMap::new();
+        for (qi_key, quant_inst) in Self::graph_instantiations(model, include_discovered) {
+            if !names.contains_key(qi_key) {
+                continue;
+            }
+            for t in Self::matched_terms(quant_inst) {
+                let Some(&src) = term_blame.get(t).filter(|src| names.contains_key(src)) else {
+                    continue;
+                };
+                let edge = ((src.key, src.version), (qi_key.key, qi_key.version));
+                let id = t.to_string();
+                let list = terms.entry(edge).or_default();
+                if !list.iter().any(|e| e.id == id) {
+                    list.push(EdgeTerm {
+                        term: term_label(model, t),
+                        id,
+                    });
+                }
+            }
+        }
+        terms
+    }
+
+    fn make_instantiation_graph(
+        model: &Model,
+        names: &HashMap<QiKey, &str>,
+        term_blame: &HashMap<&Ident, &QiKey>,
+        options: &ProfilerOptions,
+    ) -> anyhow::Result<InstantiationGraph> {
+        const PROGRESS_EVERY: usize = 100_000;
+        let quantifier_inst_matches: Vec<_> =
+            Self::graph_instantiations(model, options.include_discovered)
+                .filter(|(qi_key, _)| names.contains_key(qi_key))
+                .collect();
+        let in_graph: HashSet<&QiKey> = quantifier_inst_matches.iter().map(|(k, _)| *k).collect();
+        let resolved = AtomicUsize::new(0);
+
+        // Resolve who to blame for each instantiation in parallel; the
+        // results only differ in order, which the merge below ignores
+        let blamed = quantifier_inst_matches
+            .par_iter()
+            .map(|(qi_key, quant_inst)| {
+                let done = resolved.fetch_add(1, Ordering::Relaxed) + 1;
+                if options.verbose && done % PROGRESS_EVERY == 0 {
+                    eprintln!(
+                        "  {} of {} instantiations resolved",
+                        done,
+                        quantifier_inst_matches.len()
+                    );
+                }
+                let mut producers = Vec::new();
+                for t in Self::matched_terms(quant_inst) {
+                    // Nobody to blame for terms of the input problem
+                    let Some(&qi_responsible) = term_blame.get(t) else {
+                        continue;
+                    };
+                    if in_graph.contains(qi_responsible) {
+                        producers.push((*qi_responsible, **qi_key));
+                    } else if names.contains_key(qi_responsible) {
+                        anyhow::bail!(
+                            "instantiation {:#x}/{} produced term {} but is not in the graph",
+                            qi_responsible.key,
+                            qi_responsible.version,
+                            t
+                        );
+                    }
+                    // Otherwise skipped along with its unnamed quantifier
+                }
+                Ok(producers)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        // Create a graph over QuantifierInstances,
+        // where U->V if U produced an e-term that
+        // triggered V
+        let mut graph: BTreeMap<QiKey, BTreeSet<QiKey>> = quantifier_inst_matches
+            .iter()
+            .map(|(qi_key, _)| (**qi_key, BTreeSet::new()))
+            .collect();
+        let mut weights: HashMap<Edge, u64> = HashMap::new();
+        for (src, tgt) in blamed.into_iter().flatten() {
+            graph.entry(src).or_default().insert(tgt);
+            let edge = ((src.key, src.version), (tgt.key, tgt.version));
+            *weights.entry(edge).or_default() += 1;
+        }
+
+        let key = |k: &QiKey| (k.key, k.version);
+        let edges = graph
+            .iter()
+            .filter(|(_, tgts)| !tgts.is_empty())
+            .map(|(src, tgts)| (key(src), tgts.iter().map(key).collect()))
+            .collect();
+        let discovered = graph
+            .keys()
+            .filter(|k| matches!(model.instantiations()[k].frame, QiFrame::Discovered { .. }))
+            .map(key)
+            .collect();
+        Ok(InstantiationGraph {
+            edges,
+            names: graph
+                .keys()
+                .map(|k| (key(k), names[k].to_owned()))
+                .collect(),
+            nodes: graph.keys().map(key).collect(),
+            discovered,
+            weights,
+            cycles: HashMap::new(),
+            reverse: OnceLock::new(),
+        })
+    }
+
+    /// The instantiation graph with one node per quantifier, see
+    /// `InstantiationGraph::by_quantifier`. Self-edges stand for quantifiers
+    /// that trigger themselves.
+    pub fn aggregate_by_quantifier(&self) -> InstantiationGraph {
+        self.instantiation_graph.by_quantifier()
+    }
+
+    /// Drop every quantifier for which `keep` is false from the stats, the
+    /// graph and every per-quantifier report. Percentages are then relative
+    /// to what remains.
+    pub fn retain_quantifiers(&mut self, keep: impl Fn(&str) -> bool) {
+        self.quantifier_stats.retain(|cost| keep(&cost.quant));
+        self.discovered.retain(|d| keep(&d.quantifier));
+        self.orphans.retain(|o| keep(&o.quantifier));
+        self.raw_matches.retain(|m| keep(&m.quantifier));
+        self.enodes.retain(|name, _| keep(name));
+        self.lifespans.retain(|name, _| keep(name));
+        self.equality_merges.retain(|name, _| keep(name));
+        for t in &mut self.trigger_terms {
+            t.per_quantifier.retain(|name, _| keep(name));
+            t.matches = t.per_quantifier.values().sum();
+            t.quantifiers = t.per_quantifier.len();
+        }
+        self.trigger_terms.retain(|t| t.matches > 0);
+        sort_trigger_terms(&mut self.trigger_terms);
+        let graph = &self.instantiation_graph;
+        let nodes: HashSet<(u64, usize)> = graph
+            .names
+            .iter()
+            .filter(|(_, name)| keep(name.as_str()))
+            .map(|(k, _)| *k)
+            .collect();
+        self.instantiation_graph = graph.restrict_to(&nodes);
+        let graph = &self.instantiation_graph;
+        self.edge_terms
+            .retain(|(src, tgt), _| graph.nodes.contains(src) && graph.nodes.contains(tgt));
+    }
+
+    /// Keep only the instantiations reachable from those of `quantifier`, or
+    /// with `upstream` the ones they can be reached from, in at most
+    /// `max_depth` edges if given, and in the stats the quantifiers left in
+    /// the graph. Fails, suggesting similar names, if no graph node belongs
+    /// to `quantifier`.
+    pub fn focus_on(
+        &mut self,
+        quantifier: &str,
+        upstream: bool,
+        max_depth: Option<usize>,
+    ) -> anyhow::Result<()> {
+        let graph = &self.instantiation_graph;
+        let starts: Vec<(u64, usize)> = graph
+            .names
+            .iter()
+            .filter(|(_, name)| name.as_str() == quantifier)
+            .map(|(k, _)| *k)
+            .collect();
+        if starts.is_empty() {
+            let names: BTreeSet<&str> = graph.names.values().map(String::as_str).collect();
+            let close = close_matches(quantifier, names);
+            if close.is_empty() {
+                anyhow::bail!(
+                    "No quantifier named `{}` in the instantiation graph",
+                    quantifier
+                );
+            }
+            anyhow::bail!(
+                "No quantifier named `{}` in the instantiation graph; close matches:\n  {}",
+                quantifier,
+                close.join("\n  ")
+            );
+        }
+        self.keep_reachable(starts, upstream, max_depth);
+        Ok(())
+    }
+
+    /// Keep only the instantiations at most `max_depth` edges away from one
+    /// without incoming edges, and in the stats the quantifiers left in the
+    /// graph.
+    pub fn keep_within_depth(&mut self, max_depth: usize) {
+        let graph = &self.instantiation_graph;
+        let targets: HashSet<&(u64, usize)> = graph.edges.values().flatten().collect();
+        let roots: Vec<(u64, usize)> = graph
+            .nodes
+            .iter()
+            .filter(|k| !targets.contains(k))
+            .copied()
+            .collect();
+        self.keep_reachable(roots, false, Some(max_depth));
+    }
+
+    /// See `InstantiationGraph::reachable`.
+    fn keep_reachable(
+        &mut self,
+        starts: Vec<(u64, usize)>,
+        upstream: bool,
+        max_depth: Option<usize>,
+    ) {
+        let graph = &self.instantiation_graph;
+        let keep = graph.reachable(starts, upstream, max_depth);
+        let kept: HashSet<&str> = keep.iter().map(|k| graph.names[k].as_str()).collect();
+        self.quantifier_stats
+            .retain(|cost| kept.contains(cost.quant.as_str()));
+        self.instantiation_graph = graph.restrict_to(&keep);
+    }
+
+    /// Keep the `n` highest-ranked quantifiers in the stats, and in the graph
+    /// their instantiations plus the direct predecessors and successors of
+    /// those. Percentages are then relative to the kept quantifiers.
+    pub fn keep_top(&mut self, n: usize) {
+        self.quantifier_stats.truncate(n);
+        let top: HashSet<&str> = self
+            .quantifier_stats
+            .iter()
+            .map(|c| c.quant.as_str())
+            .collect();
+        let graph = &self.instantiation_graph;
+        let mut keep: HashSet<(u64, usize)> = graph
+            .nodes
+            .iter()
+            .filter(|k| top.contains(graph.names[*k].as_str()))
+            .copied()
+            .collect();
+        for (src, tgts) in &graph.edges {
+            let src_top = top.contains(graph.names[src].as_str());
+            for tgt in tgts {
+                if src_top {
+                    keep.insert(*tgt);
+                } else if top.contains(graph.names[tgt].as_str()) {
+                    keep.insert(*src);
+                }
+            }
+        }
+        self.instantiation_graph = graph.restrict_to(&keep);
+    }
+
+    /// Re-sort `quantifier_stats` by `score`, highest first, and by name
+    /// among equal scores.
+    pub fn rank_by(&mut self, score: impl Fn(&QuantCost, &InstantiationGraph) -> f64) {
+        let graph = &self.instantiation_graph;
+        let mut scored: Vec<(f64, QuantCost)> = self
+            .quantifier_stats
+            .drain(..)
+            .map(|cost| (score(&cost, graph), cost))
+            .collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.quant.cmp(&b.1.quant)));
+        self.quantifier_stats = scored.into_iter().map(|(_, cost)| cost).collect();
+    }
+
+    /// Re-sort `quantifier_stats` by `key`: largest first, except by name.
+    pub fn sort_stats(&mut self, key: SortKey) {
+        match key {
+            SortKey::Product => self.rank_by(default_cost),
+            SortKey::Instantiations => self.rank_by(|cost, _| cost.instantiations as f64),
+            SortKey::Cost => self.rank_by(|cost, _| cost.cost as f64),
+            SortKey::Name => self.quantifier_stats.sort_by(|a, b| a.quant.cmp(&b.quant)),
+        }
+    }
+
+    /// Replace every quantifier name in the stats, the instantiation graph
+    /// and the term blame with `rename(name)`, merging the stats of names
+    /// that end up equal. The trigger graph keeps the original names.
+    pub fn rename_quantifiers(&mut self, rename: impl Fn(&str) -> String) {
+        let mut merged: Vec<QuantCost> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        for cost in self.quantifier_stats.drain(..) {
+            let name = rename(&cost.quant);
+            self.raw_names
+                .entry(name.clone())
+                .or_default()
+                .insert(cost.quant.clone());
+            match index.get(&name) {
+                Some(&i) => {
+                    merged[i].instantiations += cost.instantiations;
+                    merged[i].cost += cost.cost;
+                }
+                None => {
+                    index.insert(name.clone(), merged.len());
+                    merged.push(QuantCost {
+                        quant: name,
+                        ..cost
+                    });
+                }
+            }
+        }
+        self.quantifier_stats = merged;
+        self.rank_by(default_cost);
+
+        for name in self.instantiation_graph.names.values_mut() {
+            *name = rename(name);
+        }
+        for name in self.term_blame.quantifiers.values_mut() {
+            *name = rename(name);
+        }
+        let mut discovered: Vec<DiscoveredCount> = Vec::new();
+        for d in self.discovered.drain(..) {
+            let name = rename(&d.quantifier);
+            match discovered.iter_mut().find(|m| m.quantifier == name) {
+                Some(m) => m.instantiations += d.instantiations,
+                None => discovered.push(DiscoveredCount {
+                    quantifier: name,
+                    ..d
+                }),
+            }
+        }
+        sort_discovered(&mut discovered);
+        self.discovered = discovered;
+        let mut enodes: HashMap<String, u64> = HashMap::new();
+        for (name, count) in self.enodes.drain() {
+            *enodes.entry(rename(&name)).or_default() += count;
+        }
+        self.enodes = enodes;
+        let mut lifespans: HashMap<String, (usize, usize)> = HashMap::new();
+        for (name, (first, last)) in self.lifespans.drain() {
+            let span = lifespans.entry(rename(&name)).or_insert((first, last));
+            *span = (span.0.min(first), span.1.max(last));
+        }
+        self.lifespans = lifespans;
+        let mut equality_merges: BTreeMap<String, Vec<EqualityMerge>> = BTreeMap::new();
+        for (name, merges) in std::mem::take(&mut self.equality_merges) {
+            equality_merges
+                .entry(rename(&name))
+                .or_default()
+                .extend(merges);
+        }
+        self.equality_merges = equality_merges;
+        for t in &mut self.trigger_terms {
+            let mut per_quantifier: BTreeMap<String, u64> = BTreeMap::new();
+            for (name, matches) in std::mem::take(&mut t.per_quantifier) {
+                *per_quantifier.entry(rename(&name)).or_default() += matches;
+            }
+            t.quantifiers = per_quantifier.len();
+            t.per_quantifier = per_quantifier;
+        }
+    }
+
+    /// Fold the profile of another trace, the `index`th of those merged, into
+    /// this one: stats, orphan, discovered and enode counts are summed by
+    /// quantifier name and the graphs unioned. Keys are only unique within
+    /// one run, so the keys of `other` get `index` as their top byte; index 0
+    /// keeps its keys. That fails for more than `MAX_MERGED_TRACES` traces
+    /// and for keys that already use the top byte. The term blame, trigger
+    /// graph, raw matches, trigger and edge terms and equality merges stay
+    /// those of `self`, whose enode identifiers they refer to.
+    pub fn merge(&mut self, other: Profiler, index: usize) -> anyhow::Result<()> {
+        if index >= MAX_MERGED_TRACES {
+            anyhow::bail!(
+                "cannot merge more than {} traces, their instantiation keys would collide",
+                MAX_MERGED_TRACES
+            );
+        }
+        let top_byte = |k: &&(u64, usize)| k.0 >> 56;
+        let clash = other
+            .instantiation_graph
+            .nodes
+            .iter()
+            .filter(|k| top_byte(k) != 0)
+            .min()
+            .or_else(|| {
+                self.instantiation_graph
+                    .nodes
+                    .iter()
+                    .filter(|k| top_byte(k) == index as u64)
+                    .min()
+            });
+        if let Some(key) = clash {
+            anyhow::bail!(
+                "instantiation key {:#x} is too large to tell the merged traces apart \
+                 (keys must be below 2^56)",
+                key.0
+            );
+        }
+
+        let mut stats: Vec<QuantCost> = self.quantifier_stats.drain(..).collect();
+        let mut position: HashMap<String, usize> = stats
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.quant.clone(), i))
+            .collect();
+        for cost in other.quantifier_stats {
+            match position.get(&cost.quant) {
+                Some(&i) => {
+                    stats[i].instantiations += cost.instantiations;
+                    stats[i].cost += cost.cost;
+                }
+                None => {
+                    position.insert(cost.quant.clone(), stats.len());
+                    stats.push(cost);
+                }
+            }
+        }
+        self.quantifier_stats = stats;
+        let namespace = (index as u64) << 56;
+        self.instantiation_graph
+            .union(other.instantiation_graph.namespaced(namespace));
+        self.rank_by(default_cost);
+
+        for o in other.orphans {
+            match self
+                .orphans
+                .iter_mut()
+                .find(|m| m.quantifier == o.quantifier)
+            {
+                Some(m) => {
+                    m.matches += o.matches;
+                    m.orphan_matches += o.orphan_matches;
+                    m.orphan_terms += o.orphan_terms;
+                }
+                None => self.orphans.push(o),
+            }
+        }
+        self.orphans.sort_by(|a, b| {
+            b.orphan_matches
+                .cmp(&a.orphan_matches)
+                .then_with(|| a.quantifier.cmp(&b.quantifier))
+        });
+        for d in other.discovered {
+            match self
+                .discovered
+                .iter_mut()
+                .find(|m| m.quantifier == d.quantifier)
+            {
+                Some(m) => m.instantiations += d.instantiations,
+                None => self.discovered.push(d),
+            }
+        }
+        sort_discovered(&mut self.discovered);
+        for (name, count) in other.enodes {
+            *self.enodes.entry(name).or_default() += count;
+        }
+        for (name, (first, last)) in other.lifespans {
+            let span = self.lifespans.entry(name).or_insert((first, last));
+            *span = (span.0.min(first), span.1.max(last));
+        }
+        for (name, raw) in other.raw_names {
+            self.raw_names.entry(name).or_default().extend(raw);
+        }
+
+        let unkept = other.consistency.total - other.consistency.issues.len();
+        for issue in other.consistency.issues {
+            self.consistency.record(issue);
+        }
+        self.consistency.total += unkept;
+        self.anonymous_quantifiers += other.anonymous_quantifiers;
+        self.ignored_lines += other.ignored_lines;
+        self.truncation = match (self.truncation.take(), other.truncation) {
+            (Some(a), Some(b)) => Some(format!("{}; {}", a, b)),
+            (a, b) => a.or(b),
+        };
+        self.timings.line_count += other.timings.line_count;
+        self.timings.parse += other.timings.parse;
+        self.timings.graph += other.timings.graph;
+        self.timings.stats += other.timings.stats;
+        Ok(())
+    }
+
+    /// Quantifiers by the summed centrality of their instantiations, highest
+    /// first, and then by name. Betweenness is used unless the graph has more
+    /// than `max_betweenness_nodes` nodes, in which case degree centrality
+    /// is; the measure used is returned too.
+    pub fn centrality(&self, max_betweenness_nodes: usize) -> (Centrality, Vec<(String, f64)>) {
+        let graph = &self.instantiation_graph;
+        let (measure, scores) = if graph.nodes.len() <= max_betweenness_nodes {
+            (Centrality::Betweenness, graph.betweenness_centrality())
+        } else {
+            (Centrality::Degree, graph.degree_centrality())
+        };
+        let mut sums: HashMap<&str, f64> = HashMap::new();
+        for (k, score) in scores {
+            *sums.entry(graph.names[&k].as_str()).or_default() += score;
+        }
+        let mut ranked: Vec<(String, f64)> = sums
+            .into_iter()
+            .map(|(name, score)| (name.to_owned(), score))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        (measure, ranked)
+    }
+
+    /// `InstantiationGraph::depths` per quantifier, in the order of
+    /// `quantifier_stats`. Quantifiers without graph nodes are left out.
+    pub fn depth_histograms(&self) -> Vec<DepthHistogram> {
+        let graph = &self.instantiation_graph;
+        let depths = graph.depths();
+        let mut histograms: HashMap<&str, DepthHistogram> = HashMap::new();
+        for node in &graph.nodes {
+            let name = graph.names[node].as_str();
+            let histogram = histograms.entry(name).or_insert_with(|| DepthHistogram {
+                quantifier: name.to_owned(),
+                counts: Vec::new(),
+                unreachable: 0,
+            });
+            match depths.get(node) {
+                Some(&depth) => {
+                    if histogram.counts.len() <= depth {
+                        histogram.counts.resize(depth + 1, 0);
+                    }
+                    histogram.counts[depth] += 1;
+                }
+                None => histogram.unreachable += 1,
+            }
+        }
+        self.quantifier_stats
+            .iter()
+            .filter_map(|cost| histograms.remove(cost.quant.as_str()))
+            .collect()
+    }
+
+    /// Instantiations that start a chain: no incoming edges but at least one
+    /// outgoing. Each comes with its quantifier and the number of other
+    /// instantiations reachable from it, largest first.
+    pub fn roots(&self) -> Vec<((u64, usize), String, usize)> {
+        let graph = &self.instantiation_graph;
+        let targets: HashSet<&(u64, usize)> = graph.edges.values().flatten().collect();
+        let mut roots: Vec<((u64, usize), String, usize)> = graph
+            .edges
+            .iter()
+            .filter(|(src, tgts)| !tgts.is_empty() && !targets.contains(src))
+            .map(|(src, _)| {
+                (
+                    *src,
+                    graph.names[src].clone(),
+                    graph.descendants(*src).len() - 1,
+                )
+            })
+            .collect();
+        roots.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| a.0.cmp(&b.0)));
+        roots
+    }
+
+    /// The quantifiers whose instantiations directly triggered instantiations
+    /// of `quantifier`, with the number of edges each contributed, most first.
+    /// `None` if no graph node belongs to `quantifier`.
+    pub fn feeders(&self, quantifier: &str) -> Option<Vec<(String, u64)>> {
+        let graph = &self.instantiation_graph;
+        let targets: HashSet<&(u64, usize)> = graph
+            .names
+            .iter()
+            .filter(|(_, name)| name.as_str() == quantifier)
+            .map(|(k, _)| k)
+            .collect();
+        if targets.is_empty() {
+            return None;
+        }
+        let mut counts: HashMap<&str, u64> = HashMap::new();
+        for (src, tgts) in &graph.edges {
+            let n = tgts.iter().filter(|t| targets.contains(t)).count() as u64;
+            if n > 0 {
+                *counts.entry(graph.names[src].as_str()).or_default() += n;
+            }
+        }
+        let mut feeders: Vec<(String, u64)> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_owned(), count))
+            .collect();
+        feeders.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        Some(feeders)
+    }
+
+    /// Heuristic suggestions for the proof author, worst offenders first.
+    /// See `InstantiationGraph::matching_loops` for `loops_ignore_discovered`.
+    pub fn advise(&self, loops_ignore_discovered: bool) -> Vec<String> {
+        const TOP: usize = 5;
+        const SHARE: f64 = 10.0;
+        let total = self.total_instantiations();
+        let mut advice = Vec::new();
+        for cost in self.quantifier_stats.iter().take(TOP) {
+            let share = percentage(cost.instantiations, total);
+            if share >= SHARE {
+                advice.push(format!(
+                    "`{}` accounts for {:.1}% of all instantiations ({}): consider a more restrictive `:pattern` for it",
+                    cost.quant, share, cost.instantiations
+                ));
+            }
+        }
+
+        let quantifiers = if loops_ignore_discovered {
+            self.instantiation_graph
+                .without_discovered()
+                .by_quantifier()
+        } else {
+            self.instantiation_graph.by_quantifier()
+        };
+        for cycle in quantifiers.matching_loops(false) {
+            let mut names: Vec<&str> = cycle
+                .iter()
+                .map(|k| quantifiers.names[k].as_str())
+                .collect();
+            names.sort();
+            if let [name] = names[..] {
+                advice.push(format!(
+                    "`{}` triggers itself: its body likely produces a term matching its own trigger (matching loop)",
+                    name
+                ));
+            } else {
+                advice.push(format!(
+                    "`{}` participate in a matching loop: check whether each body produces terms matching the others' triggers",
+                    names.join("`, `")
+                ));
+            }
+        }
+        advice
+    }
+
+    /// Cycles of the instantiation graph, largest first, each with one
+    /// concrete cycle through it to show how the loop closes. See
+    /// `InstantiationGraph::matching_loops` for `ignore_discovered`.
+    pub fn matching_loops(&self, ignore_discovered: bool) -> Vec<MatchingLoop> {
+        let graph = &self.instantiation_graph;
+        let mut loops: Vec<MatchingLoop> = graph
+            .matching_loops(ignore_discovered)
+            .into_iter()
+            .map(|mut scc| {
+                scc.sort();
+                let component: HashSet<(u64, usize)> = scc.iter().copied().collect();
+                let cycle = graph
+                    .cycle_through(scc[0], &component)
+                    .iter()
+                    .map(|k| graph.names[k].clone())
+                    .collect();
+                MatchingLoop {
+                    instantiations: scc,
+                    cycle,
+                }
+            })
+            .collect();
+        loops.sort_by(|a, b| {
+            b.instantiations
+                .len()
+                .cmp(&a.instantiations.len())
+                .then_with(|| a.instantiations.cmp(&b.instantiations))
+        });
+        loops
+    }
+
+    /// Join the statistics of `before` and `self` by quantifier name, biggest
+    /// absolute change first. Quantifiers missing from one side count as 0
+    /// there.
+    pub fn compare(&self, before: &Profiler) -> Vec<QuantifierDelta> {
+        let mut counts: BTreeMap<&str, (u64, u64)> = BTreeMap::new();
+        for cost in &before.quantifier_stats {
+            counts.entry(&cost.quant).or_default().0 += cost.instantiations;
+        }
+        for cost in &self.quantifier_stats {
+            counts.entry(&cost.quant).or_default().1 += cost.instantiations;
+        }
+        let mut deltas: Vec<QuantifierDelta> = counts
+            .into_iter()
+            .map(|(quant, (before, after))| QuantifierDelta {
+                quant: quant.to_owned(),
+                before,
+                after,
+            })
+            .collect();
+        // Stable, so equal changes stay in name order
+        deltas.sort_by_key(|d| std::cmp::Reverse(d.delta().unsigned_abs()));
+        deltas
+    }
+
+    /// `Profiler::compare` as a table, with a total row and a note of how
+    /// many quantifiers changed too little for `threshold` to be listed.
+    pub fn render_comparison(&self, before: &Profiler, threshold: &DiffThreshold) -> String {
+        let row = |d: &QuantifierDelta| {
+            let change = if d.before == 0 {
+                "new".to_owned()
+            } else {
+                format!("{:+.1}%", 100.0 * d.delta() as f64 / d.before as f64)
+            };
+            format!(
+                "{:>10} {:>10} {:>+10} {:>9}  {}\n",
+                d.before,
+                d.after,
+                d.delta(),
+                change,
+                d.quant
+            )
+        };
+        let mut out = format!(
+            "{:>10} {:>10} {:>10} {:>9}  quantifier\n",
+            "before", "after", "delta", "change"
+        );
+        let mut hidden = 0;
+        for d in &self.compare(before) {
+            if threshold.passes(d) {
+                out.push_str(&row(d));
+            } else {
+                hidden += 1;
+            }
+        }
+        out.push_str(&row(&QuantifierDelta {
+            quant: "(total)".to_owned(),
+            before: before.total_instantiations(),
+            after: self.total_instantiations(),
+        }));
+        if hidden > 0 {
+            out.push_str(&format!(
+                "({} quantifiers below the diff threshold not shown)\n",
+                hidden
+            ));
+        }
+        out
+    }
+
+    /// The instantiation that produced `enode`, and the name of its
+    /// quantifier. `None` for terms that no instantiation in the graph
+    /// produced, e.g. those from the input problem.
+    pub fn blame_for(&self, enode: &Ident) -> Option<(QiKey, String)> {
+        let qi_key = *self.term_blame.producers.get(enode)?;
+        let name = self.term_blame.quantifiers[&qi_key].clone();
+        Some((qi_key, name))
+    }
+
+    /// The chain of blame for the enode with trace id `id` (`#123`, or just
+    /// `123`) back to a root: the instantiation that produced it, then one
+    /// whose terms triggered that instantiation, and so on. Where several
+    /// did, the one with the smallest key is followed, and a cycle ends the
+    /// chain. `None` like for `Profiler::blame_for`.
+    pub fn blame_chain(&self, id: &str) -> Option<Vec<((u64, usize), String)>> {
+        let id = if id.starts_with('#') {
+            id.to_owned()
+        } else {
+            format!("#{}", id)
+        };
+        let enode = self
+            .term_blame
+            .producers
+            .keys()
+            .find(|enode| enode.to_string() == id)?;
+        let (qi_key, name) = self.blame_for(enode)?;
+        let graph = &self.instantiation_graph;
+        let mut key = (qi_key.key, qi_key.version);
+        let mut chain = vec![(key, name)];
+        let mut seen = HashSet::from([key]);
+        while let Some(prev) = graph.predecessors(key).filter(|k| !seen.contains(k)).min() {
+            seen.insert(prev);
+            chain.push((prev, graph.names[&prev].clone()));
+            key = prev;
+        }
+        Some(chain)
+    }
+
+    pub fn total_instantiations(&self) -> u64 {
+        self.quantifier_stats
+            .iter()
+            .fold(0, |acc, cost| acc + cost.instantiations)
+    }
+
+    /// `first..=last` version of `quantifier`, or `-` if it has no
+    /// instantiations in the graph.
+    fn lifespan_label(&self, quantifier: &str) -> String {
+        match self.lifespans.get(quantifier) {
+            Some((first, last)) => format!("{}..={}", first, last),
+            None => "-".to_owned(),
+        }
+    }
+
+    /// Totals over the whole profile, printed ahead of the per-quantifier
+    /// stats.
+    pub fn render_summary(&self) -> String {
+        let graph = &self.instantiation_graph;
+        let mut out = format!("Quantifiers:    {}\n", self.quantifier_stats.len());
+        out.push_str(&format!("Instantiations: {}\n", self.total_instantiations()));
+        out.push_str(&format!("Graph nodes:    {}\n", graph.nodes.len()));
+        out.push_str(&format!("Graph edges:    {}\n", graph.edge_count()));
+        out.push_str(&format!("Roots:          {}\n", self.roots().len()));
+        let cycles = if graph.matching_loops(false).is_empty() {
+            "no"
+        } else {
+            "yes (see --loops)"
+        };
+        out.push_str(&format!("Cycles:         {}\n", cycles));
+        if self.anonymous_quantifiers > 0 {
+            out.push_str(&format!(
+                "Unnamed:        {} (shown as <anon:ID>)\n",
+                self.anonymous_quantifiers
+            ));
+        }
+        out.push('\n');
+        out
+    }
+
+    pub fn print_summary(&self) {
+        print!("{}", self.render_summary());
+    }
+
+    /// With `lifespan`, also show the versions each quantifier was active
+    /// between.
+    pub fn render_stats(&self, lifespan: bool) -> String {
+        let total = self.total_instantiations();
+        if total == 0 {
+            return "No quantifier instantiations found\n".to_owned();
+        }
+        let self_triggering: HashSet<String> = self
+            .instantiation_graph
+            .self_triggering()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        let mut out = String::new();
+        for cost in &self.quantifier_stats {
+            let span = if lifespan {
+                format!(", versions {}", self.lifespan_label(&cost.quant))
+            } else {
+                String::new()
+            };
+            let marker = if self_triggering.contains(&cost.quant) {
+                " [SELF-TRIGGERING]"
+            } else {
+                ""
+            };
+            out.push_str(&format!(
+                "{}, {} enodes{}{} \n\n",
+                stat_line(cost, total),
+                self.enodes_of(&cost.quant),
+                span,
+                marker
+            ));
+        }
+        out
+    }
+
+    /// See `Profiler::render_stats` for `lifespan`.
+    pub fn print_stats(&self, lifespan: bool) {
+        print!("{}", self.render_stats(lifespan));
+    }
+
+    /// The stats as a table with aligned columns. Names too long for their
+    /// column are cut short and given a footnote with the full name. See
+    /// `Profiler::render_stats` for `lifespan`.
+    pub fn render_table(&self, lifespan: bool) -> String {
+        const NAME_WIDTH: usize = 50;
+        let total = self.total_instantiations();
+        let mut out = format!(
+            "{:>5}  {:<NAME_WIDTH$}  {:>14}  {:>12}  {:>12}  {:>8}",
+            "Rank", "Quantifier", "Instantiations", "Cost", "Enodes", "% total"
+        );
+        if lifespan {
+            out.push_str(&format!("  {:>15}", "Versions"));
+        }
+        out.push('\n');
+        let mut footnotes = Vec::new();
+        for (rank, cost) in self.quantifier_stats.iter().enumerate() {
+            let name = if cost.quant.chars().count() > NAME_WIDTH {
+                footnotes.push(&cost.quant);
+                let mark = format!(" [{}]", footnotes.len());
+                let short = truncate_name(&cost.quant, NAME_WIDTH - mark.len());
+                format!("{}{}", short, mark)
+            } else {
+                cost.quant.clone()
+            };
+            out.push_str(&format!(
+                "{:>5}  {:<NAME_WIDTH$}  {:>14}  {:>12}  {:>12}  {:>7.1}%",
+                rank + 1,
+                name,
+                cost.instantiations,
+                cost.cost,
+                self.enodes_of(&cost.quant),
+                percentage(cost.instantiations, total)
+            ));
+            if lifespan {
+                out.push_str(&format!("  {:>15}", self.lifespan_label(&cost.quant)));
+            }
+            out.push('\n');
+        }
+        if !footnotes.is_empty() {
+            out.push('\n');
+            for (i, name) in footnotes.iter().enumerate() {
+                out.push_str(&format!("[{}] {}\n", i + 1, name));
+            }
+        }
+        out
+    }
+
+    /// See `Profiler::render_stats` for `lifespan`.
+    pub fn render_markdown(&self, lifespan: bool) -> String {
+        const MAX_NAME_LEN: usize = 60;
+        let total = self.total_instantiations();
+        let mut out = String::new();
+        if lifespan {
+            out.push_str(
+                "| Quantifier | Instantiations | Cost | Enodes | % of total | Versions |\n",
+            );
+            out.push_str("|---|---:|---:|---:|---:|---:|\n");
+        } else {
+            out.push_str("| Quantifier | Instantiations | Cost | Enodes | % of total |\n");
+            out.push_str("|---|---:|---:|---:|---:|\n");
+        }
+        for cost in &self.quantifier_stats {
+            out.push_str(&format!(
+                "| `{}` | {} | {} | {} | {:.1}% |",
+                truncate_name(&cost.quant, MAX_NAME_LEN).replace('|', "\\|"),
+                cost.instantiations,
+                cost.cost,
+                self.enodes_of(&cost.quant),
+                percentage(cost.instantiations, total)
+            ));
+            if lifespan {
+                out.push_str(&format!(" {} |", self.lifespan_label(&cost.quant)));
+            }
+            out.push('\n');
+        }
+
+        let graph = &self.instantiation_graph;
+        let has_incoming: HashSet<&(u64, usize)> = graph.edges.values().flatten().collect();
+        let roots = graph
+            .edges
+            .keys()
+            .filter(|k| !has_incoming.contains(k))
+            .count();
+        let cyclic = petgraph::algo::is_cyclic_directed(&generate_graph(graph));
+        out.push_str("\n**Summary**\n\n");
+        out.push_str(&format!(
+            "- {} quantifiers, {} instantiations\n",
+            self.quantifier_stats.len(),
+            total
+        ));
+        out.push_str(&format!(
+            "- {} root instantiations (nothing triggered them, but they triggered others)\n",
+            roots
+        ));
+        out.push_str(if cyclic {
+            "- Matching loops: the instantiation graph contains cycles\n"
+        } else {
+            "- Matching loops: none found\n"
+        });
+        out
+    }
+
+    /// `Profiler::stats_report` as CSV, one row per quantifier in the order
+    /// of `Profiler::quantifier_stats`.
+    pub fn render_csv(&self) -> String {
+        let mut out =
+            String::from("quantifier,instantiations,cost,percentage,in_degree,out_degree,enodes\n");
+        for q in self.stats_report().quantifiers {
+            out.push_str(&format!(
+                "{},{},{},{:.2},{},{},{}\n",
+                csv_field(&q.quantifier),
+                q.instantiations,
+                q.cost,
+                q.percentage,
+                q.in_degree,
+                q.out_degree,
+                q.enodes
+            ));
+        }
+        out
+    }
+
+    /// The instantiation graph as SVG, in `InstantiationGraph::layers`, with
+    /// every node outlined in the color of its quantifier on the `heat`
+    /// scale and the full name as its tooltip.
+    pub fn render_svg(&self) -> String {
+        const COLUMN: f64 = 200.;
+        const ROW: f64 = 80.;
+        const WIDTH: f64 = 180.;
+        const HEIGHT: f64 = 30.;
+        const MARGIN: f64 = 20.;
+        const LABEL_LEN: usize = 24;
+        let graph = &self.instantiation_graph;
+        let counts: HashMap<&str, u64> = self
+            .quantifier_stats
+            .iter()
+            .map(|cost| (cost.quant.as_str(), cost.instantiations))
+            .collect();
+        let max = counts.values().copied().max().unwrap_or(0);
+        let layers = graph.layers();
+        let columns = layers.values().map(|(c, _)| c + 1).max().unwrap_or(0);
+        let rows = layers.values().map(|(_, r)| r + 1).max().unwrap_or(0);
+        // Top left corner of a node's box
+        let corner = |k: &(u64, usize)| {
+            let (column, row) = layers[k];
+            (MARGIN + column as f64 * COLUMN, MARGIN + row as f64 * ROW)
+        };
+
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"12\">\n",
+            2. * MARGIN + columns.saturating_sub(1) as f64 * COLUMN + WIDTH,
+            2. * MARGIN + rows.saturating_sub(1) as f64 * ROW + HEIGHT
+        );
+        out.push_str("  <defs>\n");
+        out.push_str("    <marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" markerWidth=\"8\" markerHeight=\"8\" orient=\"auto\">\n");
+        out.push_str("      <path d=\"M 0 0 L 10 5 L 0 10 z\"/>\n");
+        out.push_str("    </marker>\n");
+        out.push_str("  </defs>\n");
+        let edges = graph.sorted_edges();
+        for (src, tgt) in edges {
+            let (x1, y1) = corner(&src);
+            let (x2, y2) = corner(&tgt);
+            out.push_str(&format!(
+                "  <line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"black\" marker-end=\"url(#arrow)\"/>\n",
+                x1 + WIDTH / 2.,
+                y1 + HEIGHT,
+                x2 + WIDTH / 2.,
+                y2
+            ));
+        }
+        for node in &graph.sorted_nodes() {
+            let name = &graph.names[node];
+            let instantiations = counts.get(name.as_str()).copied().unwrap_or(0);
+            let [r, g, b] = heat_rgb(heat(instantiations, max));
+            let (x, y) = corner(node);
+            out.push_str("  <g>\n");
+            out.push_str(&format!("    <title>{}</title>\n", xml_escape(name)));
+            out.push_str(&format!(
+                "    <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"white\" stroke=\"rgb({},{},{})\" stroke-width=\"2\"/>\n",
+                x, y, WIDTH, HEIGHT, r, g, b
+            ));
+            out.push_str(&format!(
+                "    <text x=\"{}\" y=\"{}\" text-anchor=\"middle\" dominant-baseline=\"middle\">{} (×{})</text>\n",
+                x + WIDTH / 2.,
+                y + HEIGHT / 2.,
+                xml_escape(&truncate_name(name, LABEL_LEN)),
+                instantiations
+            ));
+            out.push_str("  </g>\n");
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    /// The instantiation graph as GraphML, with the quantifier name and its
+    /// instantiation count, cost and enodes produced on every node and the weight on every
+    /// edge. Nodes and edges are in key order.
+    pub fn render_graphml(&self) -> String {
+        let graph = &self.instantiation_graph;
+        let costs: HashMap<&str, &QuantCost> = self
+            .quantifier_stats
+            .iter()
+            .map(|cost| (cost.quant.as_str(), cost))
+            .collect();
+        let id = |k: &(u64, usize)| node_id(*k);
+
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"instantiations\" for=\"node\" attr.name=\"instantiations\" attr.type=\"long\"/>\n");
+        out.push_str("  <key id=\"cost\" for=\"node\" attr.name=\"cost\" attr.type=\"long\"/>\n");
+        out.push_str(
+            "  <key id=\"enodes\" for=\"node\" attr.name=\"enodes\" attr.type=\"long\"/>\n",
+        );
+        out.push_str(
+            "  <key id=\"weight\" for=\"edge\" attr.name=\"weight\" attr.type=\"long\"/>\n",
+        );
+        out.push_str("  <graph id=\"instantiations\" edgedefault=\"directed\">\n");
+        for node in &graph.sorted_nodes() {
+            let name = &graph.names[node];
+            let (instantiations, cost) = costs
+                .get(name.as_str())
+                .map_or((0, 0), |c| (c.instantiations, c.cost));
+            out.push_str(&format!("    <node id=\"{}\">\n", id(node)));
+            out.push_str(&format!(
+                "      <data key=\"name\">{}</data>\n",
+                xml_escape(name)
+            ));
+            out.push_str(&format!(
+                "      <data key=\"instantiations\">{}</data>\n",
+                instantiations
+            ));
+            out.push_str(&format!("      <data key=\"cost\">{}</data>\n", cost));
+            out.push_str(&format!(
+                "      <data key=\"enodes\">{}</data>\n",
+                self.enodes_of(name)
+            ));
+            out.push_str("    </node>\n");
+        }
+        let edges = graph.sorted_edges();
+        for (src, tgt) in edges {
+            out.push_str(&format!(
+                "    <edge source=\"{}\" target=\"{}\">\n",
+                id(&src),
+                id(&tgt)
+            ));
+            out.push_str(&format!(
+                "      <data key=\"weight\">{}</data>\n",
+                graph.weight(src, tgt)
+            ));
+            out.push_str("    </edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+/// The ranking `Profiler` uses by default: z3tracer's cost estimate times
+/// the number of instantiations.
+pub fn default_cost(cost: &QuantCost, _: &InstantiationGraph) -> f64 {
+    cost.instantiations as f64 * cost.cost as f64
+}
+
+/// Scoring function for `Profiler::rank_by`.
+pub type CostFn = Box<dyn Fn(&QuantCost, &InstantiationGraph) -> f64>;
+
+/// The measures `Profiler::centrality` can rank by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Centrality {
+    /// See `InstantiationGraph::degree_centrality`
+    Degree,
+    /// See `InstantiationGraph::betweenness_centrality`
+    Betweenness,
+}
+
+impl Centrality {
+    pub fn label(self) -> &'static str {
+        match self {
+            Centrality::Degree => "degree",
+            Centrality::Betweenness => "betweenness",
+        }
+    }
+}
+
+/// Built-in rankings for `Profiler::rank_by`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum CostModel {
+    /// `default_cost`
+    #[default]
+    Product,
+    /// Number of instantiations
+    Instantiations,
+    /// z3tracer's cost per instantiation
+    PerInstantiation,
+    /// Instantiations transitively triggered by the quantifier's instantiations
+    Descendants,
+    /// Instantiations weighted by 1 / (1 + generation), so quantifiers early in a cascade rank first
+    Generation,
+}
+
+impl CostModel {
+    /// The scoring function, with whatever it needs from `graph` precomputed.
+    pub fn scorer(self, graph: &InstantiationGraph) -> CostFn {
+        match self {
+            CostModel::Product => Box::new(default_cost),
+            CostModel::Instantiations => {
+                Box::new(|cost: &QuantCost, _: &InstantiationGraph| cost.instantiations as f64)
+            }
+            CostModel::PerInstantiation => Box::new(|cost: &QuantCost, _: &InstantiationGraph| {
+                cost.cost as f64 / cost.instantiations.max(1) as f64
+            }),
+            CostModel::Descendants => {
+                let counts = graph.descendants_by_quantifier();
+                Box::new(move |cost: &QuantCost, _: &InstantiationGraph| {
+                    counts.get(&cost.quant).copied().unwrap_or(0) as f64
+                })
+            }
+            CostModel::Generation => {
+                let mut weights: HashMap<String, f64> = HashMap::new();
+                for (k, generation) in graph.generations() {
+                    *weights.entry(graph.names[&k].clone()).or_default() +=
+                        1.0 / (1 + generation) as f64;
+                }
+                Box::new(move |cost: &QuantCost, _: &InstantiationGraph| {
+                    weights.get(&cost.quant).copied().unwrap_or(0.0)
+                })
+            }
+        }
+    }
+}
+
+/// Orderings of the statistics for `Profiler::sort_stats`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum SortKey {
+    /// Instantiations times cost, as ranked by default
+    Product,
+    Instantiations,
+    Cost,
+    /// Alphabetically
+    Name,
+}
+
+/// Clean-up of the quantifier names generated by verification front-ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Demangle {
+    #[default]
+    None,
+    /// F*: drop the leading `@` of internal axioms and the hashes of `Tm_` names
+    Fstar,
+    /// Dafny: drop the `_module.__default.` prefix, then strip hashes
+    Dafny,
+    /// Strip trailing `@N` version suffixes and hex hashes
+    StripHash,
+}
+
+impl Demangle {
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Demangle::None => name.to_owned(),
+            Demangle::Fstar => strip_hash(name.strip_prefix('@').unwrap_or(name)).to_owned(),
+            Demangle::Dafny => {
+                let name = name
+                    .strip_prefix("_module.__default.")
+                    .or_else(|| name.strip_prefix("_module."))
+                    .unwrap_or(name);
+                strip_hash(name).to_owned()
+            }
+            Demangle::StripHash => strip_hash(name).to_owned(),
+        }
+    }
+}
+
+/// `name` without any trailing `@N` suffixes or hashes (8 or more hex digits
+/// after a `_`, `.` or `#`).
+fn strip_hash(mut name: &str) -> &str {
+    loop {
+        let before = name.len();
+        if let Some((head, tail)) = name.rsplit_once('@') {
+            if !head.is_empty() && !tail.is_empty() && tail.bytes().all(|b| b.is_ascii_digit()) {
+                name = head;
+            }
+        }
+        if let Some(i) = name.rfind(['_', '.', '#']) {
+            let tail = &name[i + 1..];
+            if i > 0 && tail.len() >= 8 && tail.bytes().all(|b| b.is_ascii_hexdigit()) {
+                name = &name[..i];
+            }
+        }
+        if name.len() == before {
+            return name;
+        }
+    }
+}
+
+/// Quantifier name patterns from allow/deny list files. Patterns may use
+/// `*` and `?` wildcards.
+#[derive(Debug, Default, Clone)]
+pub struct NameFilter {
+    pub ignore: Vec<String>,
+    pub focus: Option<Vec<String>>,
+}
+
+impl NameFilter {
+    /// One pattern per line; blank lines and lines starting with `#` are skipped.
+    pub fn read_patterns(path: &std::path::Path) -> anyhow::Result<Vec<String>> {
+        let text = std::fs::read_to_string(path)?;
+        Ok(text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_owned)
+            .collect())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ignore.is_empty() && self.focus.is_none()
+    }
+
+    pub fn allows(&self, name: &str) -> bool {
+        if self.ignore.iter().any(|p| glob_match(p, name)) {
+            return false;
+        }
+        match &self.focus {
+            Some(focus) => focus.iter().any(|p| glob_match(p, name)),
+            None => true,
+        }
+    }
+}
+
+/// Up to five of `candidates` that look like a misspelling of `name`: ones
+/// containing it or contained in it, ignoring case, and ones a few edits
+/// away. Closest first.
+fn close_matches<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Vec<&'a str> {
+    let needle = name.to_lowercase();
+    let max_distance = (needle.chars().count() / 4).max(2);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            if lower.contains(&needle) || needle.contains(&lower) {
+                return Some((0, candidate));
+            }
+            let distance = edit_distance(&needle, &lower);
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+    close.sort();
+    close.into_iter().take(5).map(|(_, c)| c).collect()
+}
+
+/// Levenshtein distance, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    let (mut pi, mut ni) = (0, 0);
+    // Position of the last `*` seen, and where in `name` it started matching
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            pi += 1;
+            ni += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((star_pi, star_ni)) = star {
+            // Let the last `*` swallow one more character and retry
+            pi = star_pi + 1;
+            ni = star_ni + 1;
+            star = Some((star_pi, star_ni + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+/// The term pretty-printed as an s-expression, or just its id if that fails.
+fn term_label(model: &Model, ident: &Ident) -> String {
+    model
+        .id_to_sexp(&BTreeMap::new(), ident)
+        .unwrap_or_else(|_| ident.to_string())
+}
+
+/// Position of `instantiations` on a log scale from 0 for none to 1 for
+/// `max`, the most instantiations of any quantifier, for coloring nodes.
+pub fn heat(instantiations: u64, max: u64) -> f32 {
+    if max == 0 {
+        return 0.;
+    }
+    (instantiations as f32).ln_1p() / (max as f32).ln_1p()
+}
+
+/// Blue for cold nodes through to red for the hottest, as RGB.
+pub fn heat_rgb(heat: f32) -> [u8; 3] {
+    let t = heat.clamp(0., 1.);
+    [(255. * t) as u8, 64, (255. * (1. - t)) as u8]
+}
+
+pub fn percentage(count: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        100.0 * count as f64 / total as f64
+    }
+}
+
+/// The line `Profiler::render_stats` gives for `cost`, `total` being the
+/// number of instantiations of all quantifiers. It ends in the product that
+/// `default_cost` ranks by, so that the order of the lines is plain.
+pub fn stat_line(cost: &QuantCost, total: u64) -> String {
+    format!(
+        "Instantiated {} {} times ({:.1}% of the total), cost {}, instantiations × cost {}",
+        cost.quant,
+        cost.instantiations,
+        percentage(cost.instantiations, total),
+        cost.cost,
+        cost.instantiations as u128 * cost.cost as u128
+    )
+}
+
+/// `field` quoted as RFC 4180 requires if it contains a delimiter, quote or
+/// line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// The name of a cycle of `size` instantiations of the quantifiers `names`
+/// once condensed: the first three names in sorted order and how many others
+/// there are, then the size, e.g. `a, b, c +2 (cycle of 9)`.
+fn cycle_label<'a>(names: impl Iterator<Item = &'a str>, size: usize) -> String {
+    const SHOWN: usize = 3;
+    let names: BTreeSet<&str> = names.collect();
+    let mut label = names
+        .iter()
+        .take(SHOWN)
+        .copied()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if names.len() > SHOWN {
+        label.push_str(&format!(" +{}", names.len() - SHOWN));
+    }
+    format!("{} (cycle of {})", label, size)
+}
+
+/// `text` with the characters that are special in XML escaped.
+fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// The identifier of an instantiation in the DOT, GraphML and graph JSON exports,
+/// e.g. `n1a_0` for key `0x1a`, version 0.
+pub fn node_id(key: (u64, usize)) -> String {
+    format!("n{:x}_{}", key.0, key.1)
+}
+
+/// Shorten `name` to at most `max` characters, marking the cut with an ellipsis.
+pub fn truncate_name(name: &str, max: usize) -> String {
+    if name.chars().count() <= max {
+        name.to_owned()
+    } else {
+        let mut short: String = name.chars().take(max.saturating_sub(1)).collect();
+        short.push('…');
+        short
+    }
+}
+
+pub type NodeData = ((u64, usize), String);
+
+/// The graph in DOT, nodes and edges in key order, each node identified by
+/// `node_id` and labelled with its quantifier name and each edge with its
+/// weight.
+pub fn render_dot(graph: &InstantiationGraph) -> String {
+    let mut out = String::from("digraph {\n");
+    dot_content(graph, &mut out);
+    out.push_str("}\n");
+    out
+}
+
+/// The trigger graph in DOT, nodes and edges in order, each node identified
+/// by `TriggerNode::id` and labelled with its name.
+pub fn render_trigger_dot(graph: &TriggerGraph) -> String {
+    let mut out = String::from("digraph {\n");
+    dot_statements(
+        &mut out,
+        graph
+            .sorted_nodes()
+            .into_iter()
+            .map(|node| (node.id(), graph.names[node].as_str())),
+        graph
+            .sorted_edges()
+            .into_iter()
+            .map(|(src, tgt)| (src.id(), tgt.id(), None)),
+    );
+    out.push_str("}\n");
+    out
+}
+
+/// The graph in DOT, laid out top-down with one `rank=same` group per
+/// generation (see `InstantiationGraph::generations`), so roots come first.
+pub fn ranked_dot(graph: &InstantiationGraph) -> String {
+    let generations = graph.generations();
+    let mut ranks: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+    for node in graph.sorted_nodes() {
+        ranks.entry(generations[&node]).or_default().push(node_id(node));
+    }
+
+    let mut out = String::from("digraph {\n    rankdir=TB\n");
+    dot_content(graph, &mut out);
+    for ids in ranks.values() {
+        out.push_str(&format!("    {{ rank=same; {}; }}\n", ids.join("; ")));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// The node and edge statements shared by `render_dot` and `ranked_dot`.
+fn dot_content(graph: &InstantiationGraph, out: &mut String) {
+    dot_statements(
+        out,
+        graph
+            .sorted_nodes()
+            .into_iter()
+            .map(|node| (node_id(node), graph.names[&node].as_str())),
+        graph.sorted_edges().into_iter().map(|(src, tgt)| {
+            let weight = graph.weight(src, tgt).to_string();
+            (node_id(src), node_id(tgt), Some(weight))
+        }),
+    );
+}
+
+/// A DOT statement for each node, given by id and label, and each edge,
+/// given by the ids of its ends and an optional label.
+fn dot_statements<'a>(
+    out: &mut String,
+    nodes: impl Iterator<Item = (String, &'a str)>,
+    edges: impl Iterator<Item = (String, String, Option<String>)>,
+) {
+    for (id, label) in nodes {
+        out.push_str(&format!(
+            "    {} [ label = \"{}\" ]\n",
+            dot_id(&id),
+            dot_escape(label)
+        ));
+    }
+    for (src, tgt, label) in edges {
+        out.push_str(&format!("    {} -> {}", dot_id(&src), dot_id(&tgt)));
+        if let Some(label) = label {
+            out.push_str(&format!(" [ label = \"{}\" ]", dot_escape(&label)));
+        }
+        out.push('\n');
+    }
+}
+
+/// `id` as a DOT identifier, quoted unless it is a plain word.
+fn dot_id(id: &str) -> String {
+    if id.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        id.to_owned()
+    } else {
+        format!("\"{}\"", dot_escape(id))
+    }
+}
+
+/// `text` as the inside of a DOT string, with quotes and backslashes escaped.
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The graph for petgraph, with `InstantiationGraph::weight` as the edge
+/// payload.
+pub fn generate_graph(graph: &InstantiationGraph) -> StableGraph<NodeData, u64, Directed> {
+    let mut g: StableGraph<NodeData, u64> = StableGraph::new();
+    let mut nodes = HashMap::new();
+    // Every node has a name and every edge endpoint is a node, whatever the
+    // trace looked like, so indexing cannot fail here
+    for node in graph.sorted_nodes() {
+        nodes.insert(node, g.add_node((node, graph.names[&node].clone())));
+    }
+    for (src, dst) in graph.sorted_edges() {
+        g.add_edge(nodes[&src], nodes[&dst], graph.weight(src, dst));
+    }
+    g
+}
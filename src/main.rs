@@ -1,315 +1,1022 @@
-use anyhow;
-use anyhow::Error;
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
-use std::io::BufRead;
-
-use z3tracer::{
-    model::QuantCost,
-    syntax::{MatchedTerm, QiFrame, QiKey},
-    Model, ModelConfig,
+#[cfg(feature = "gui")]
+mod gui;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use gordian_profiler::{
+    percentage, ranked_dot, render_dot, render_trigger_dot, Centrality, CostModel, Demangle,
+    DiffThreshold, NameFilter, Profiler, ProfilerOptions, SortKey, MAX_MERGED_TRACES,
 };
 
-use eframe::{run_native, App, CreationContext};
-use egui;
-use egui::{
-    Context,
-    epaint::TextShape, FontFamily, FontId, Rect, Rounding,Shape, Stroke, Vec2
-};
-
-use egui_graphs::{Graph, GraphView, SettingsInteraction};
-use petgraph::{stable_graph::StableGraph, Directed};
-use petgraph::dot::{Dot, Config};
 use std::fs::File;
 use std::io::prelude::*;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 
 #[derive(Subcommand, Debug)]
 enum Commands {
+    /// Open the interactive graph view after printing the stats
     Gui,
-    Dot
+    /// Write the graph to `graph.dot`
+    Dot,
+    /// Parse every `.log` file in DIR and print size, line count and parse speed as CSV
+    Bench { dir: std::path::PathBuf },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Text,
+    /// Aligned columns, one row per quantifier
+    Table,
+    /// GitHub-flavored markdown table, for pasting into issues
+    Markdown,
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
+    /// Trace to analyze; read from stdin if omitted or `-`. Repeat to merge
+    /// several traces, e.g. of incremental sessions, into one profile
+    #[arg(short, long, value_name = "PATH")]
+    file: Vec<std::path::PathBuf>,
+
+    /// Add theory/MBQI (`[inst-discovered]`) instantiations to the graph
+    #[arg(long)]
+    include_discovered: bool,
+
+    /// Print the matching loops (cycles) of the instantiation graph
+    #[arg(long)]
+    loops: bool,
+
+    /// Don't count cycles that only close through a discovered instantiation as matching loops
+    #[arg(long)]
+    loops_ignore_discovered: bool,
+
+    /// Also build a graph where trigger patterns are nodes between instantiations; it is exported whole, so it cannot be combined with the options that filter or reshape the instantiation graph
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "filter",
+            "top",
+            "dot_ranks",
+            "focus",
+            "focus_upstream",
+            "ignore_file",
+            "focus_file",
+            "largest_component",
+            "max_depth",
+            "version_window",
+            "condense",
+            "aggregate",
+            "transitive_reduction",
+            "max_edges",
+        ]
+    )]
+    trigger_graph: bool,
+
+    /// Keep only the N highest-ranked quantifiers, and in the graph their instantiations and direct neighbors
+    #[arg(long, value_name = "N")]
+    top: Option<usize>,
+
+    /// Keep only the K largest weakly-connected components of the graph
+    #[arg(long, value_name = "K", num_args = 0..=1, default_missing_value = "1")]
+    largest_component: Option<usize>,
+
+    /// File of quantifier names or glob patterns to exclude, one per line
+    #[arg(long, value_name = "PATH")]
+    ignore_file: Option<std::path::PathBuf>,
+
+    /// File of quantifier names or glob patterns to restrict the output to, one per line
+    #[arg(long, value_name = "PATH")]
+    focus_file: Option<std::path::PathBuf>,
+
+    /// Keep only quantifiers whose (raw) name matches REGEX; percentages are then of the matching ones' total
+    #[arg(long, value_name = "REGEX", value_parser = regex::Regex::new)]
+    filter: Option<regex::Regex>,
+
+    /// Keep only the instantiations transitively triggered by those of QUANTIFIER
+    #[arg(long, value_name = "QUANTIFIER")]
+    focus: Option<String>,
+
+    /// Keep only the instantiations that transitively triggered those of QUANTIFIER (with --focus: what lies between the two)
+    #[arg(long, value_name = "QUANTIFIER")]
+    focus_upstream: Option<String>,
+
+    /// With --focus or --focus-upstream, follow at most N edges from the quantifier; otherwise keep what lies within N edges of an instantiation nothing triggered
+    #[arg(long, value_name = "N")]
+    max_depth: Option<usize>,
+
+    /// Clean up front-end generated quantifier names before display and grouping (ignore/focus files still match the raw names)
+    #[arg(long, value_enum, default_value_t = Demangle::None)]
+    demangle: Demangle,
+
+    /// Merge instantiations of the same quantifier whose versions fall in the same window of size W
+    #[arg(long, value_name = "W", value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    version_window: Option<usize>,
+
+    /// Show and export the condensation: every strongly-connected component (e.g. a matching loop) as one node, which leaves a DAG
+    #[arg(long)]
+    condense: bool,
+
+    /// In the `dot` export, rank instantiations by causal generation so roots are at the top
+    #[arg(long)]
+    dot_ranks: bool,
+
+    /// Merge all instantiations of a quantifier into one node, summing the weights of merged edges
+    #[arg(long)]
+    aggregate: bool,
+
+    /// Drop edges implied by longer paths before exporting or displaying the graph
+    #[arg(long)]
+    transitive_reduction: bool,
+
+    /// Keep only the N heaviest edges in the exported and displayed graph
+    #[arg(long, value_name = "N")]
+    max_edges: Option<usize>,
+
+    /// Print the quantifiers along a longest chain of instantiations (cycles count once)
+    #[arg(long)]
+    longest_chain: bool,
+
+    /// List the instantiations that start chains (no incoming edges), by how much they reach
+    #[arg(long)]
+    roots: bool,
+
+    /// List the quantifiers that directly trigger the named quantifier, most frequent first
+    #[arg(long, value_name = "QUANTIFIER")]
+    feeders: Option<String>,
+
+    /// Print the chain of instantiations that led to the enode with this trace id, e.g. `#123`, back to a root
+    #[arg(long, value_name = "ENODE")]
+    blame: Option<String>,
+
+    /// Suggest (heuristic) trigger fixes for the worst offenders
+    #[arg(long)]
+    advise: bool,
+
+    /// Rank the ground terms matched by triggers across all quantifiers, showing the top N
+    #[arg(long, value_name = "N", num_args = 0..=1, default_missing_value = "20")]
+    by_trigger: Option<usize>,
+
+    /// Compare instantiation counts against another trace (or saved profile), taken as the baseline
+    #[arg(long, value_name = "PATH")]
+    compare: Option<std::path::PathBuf>,
+
+    /// With --compare, only list quantifiers whose count changed by at least N
+    #[arg(long, value_name = "N", default_value_t = 3)]
+    diff_threshold: u64,
+
+    /// With --compare, only list quantifiers whose count changed by at least P percent
+    #[arg(long, value_name = "P", default_value_t = 0.0)]
+    diff_threshold_pct: f64,
+
+    /// Show, per quantifier, how many of its matches were on input terms rather than on instantiation results
+    #[arg(long)]
+    orphan_report: bool,
+
+    /// List the quantifiers whose instantiations trigger instantiations of the same quantifier
+    #[arg(long)]
+    self_triggering: bool,
+
+    /// Print, for every edge, the terms the source produced that the target matched on
+    #[arg(long)]
+    explain_edges: bool,
+
+    /// Rank quantifiers by how many shortest instantiation chains pass through them (betweenness centrality)
+    #[arg(long)]
+    centrality: bool,
+
+    /// Graph size above which --centrality falls back to the much cheaper degree centrality
+    #[arg(long, value_name = "N", default_value_t = 5000)]
+    betweenness_max_nodes: usize,
+
+    /// Print how each quantifier's instantiations are spread over distances from the graph roots
+    #[arg(long)]
+    depth_histogram: bool,
+
+    /// Report how many instantiations per quantifier came from theory reasoning or MBQI rather than E-matching
+    #[arg(long)]
+    discovered: bool,
+
+    /// How to rank quantifiers in the statistics
+    #[arg(long, value_enum, default_value_t = CostModel::Product)]
+    cost_model: CostModel,
+
+    /// Order the statistics by KEY instead of the --cost-model ranking
+    #[arg(long, value_enum, value_name = "KEY")]
+    sort: Option<SortKey>,
+
+    /// Show the first and last instantiation version of each quantifier in the statistics
+    #[arg(long)]
+    lifespan: bool,
+
+    /// How to print the quantifier statistics
+    #[arg(long, value_enum, default_value_t = OutputFormat::Text)]
+    format: OutputFormat,
+
+    /// Also write the instantiation graph as GraphML (for Gephi, Cytoscape, ...)
+    #[arg(long, value_name = "PATH")]
+    graphml: Option<std::path::PathBuf>,
+
+    /// Also write the instantiation graph (or with --trigger-graph the trigger graph) as JSON nodes and edges, for web visualizers
+    #[arg(long, value_name = "PATH")]
+    graph_json: Option<std::path::PathBuf>,
+
+    /// Also draw the instantiation graph as SVG, in layers by distance from the roots
+    #[arg(long, value_name = "PATH")]
+    svg: Option<std::path::PathBuf>,
+
+    /// Also write the quantifier statistics, with graph degrees, as CSV
+    #[arg(long, value_name = "PATH")]
+    csv: Option<std::path::PathBuf>,
+
+    /// Also write the quantifier statistics, with graph degrees, as JSON
+    #[arg(long, value_name = "PATH")]
+    json: Option<std::path::PathBuf>,
+
+    /// Write every pattern match (instantiation key, quantifier and matched terms) as JSON lines
+    #[arg(long, value_name = "PATH")]
+    raw_matches: Option<std::path::PathBuf>,
+
+    /// Also write the statistics and graph as a compact binary profile, which `--file` accepts in place of a trace
+    #[arg(long, value_name = "PATH", alias = "cache-out")]
+    save_bin: Option<std::path::PathBuf>,
+
+    /// Load a profile written by `--save-bin` instead of parsing a trace; one from another build is rejected
+    #[arg(long, value_name = "PATH", conflicts_with = "file")]
+    cache_in: Option<std::path::PathBuf>,
+
+    /// Enable z3tracer's log consistency checks and report every problem they find
+    #[arg(long, alias = "consistency-checks")]
+    consistency: bool,
+
+    /// Refuse traces from Z3 versions z3tracer does not know
+    #[arg(long)]
+    check_version: bool,
+
+    /// Fail on the first trace line that cannot be parsed instead of skipping it
+    #[arg(long)]
+    strict_lines: bool,
+
+    /// Have z3tracer log the term equalities it derives, which --why-not reads; equality edges of the graph do not need it
+    #[arg(long)]
+    log_equalities: bool,
+
+    /// Show the equality merges involving the trigger terms of a quantifier that should have fired but did not (repeatable; needs --log-equalities)
+    #[arg(long, value_name = "QUANTIFIER")]
+    why_not: Vec<String>,
+
+    /// Skip instantiations of quantifiers that cannot be named (e.g. in a truncated trace) instead of failing
+    #[arg(long)]
+    lenient: bool,
+
+    /// Threads to build the instantiation graph with (default: one per core)
+    #[arg(long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Report progress and the time each phase took on stderr
     #[arg(short, long)]
-    file: std::path::PathBuf,
-    
-    #[command(subcommand)]
-    cmd: Commands,
-}
+    verbose: bool,
 
-fn process_file(path: &std::path::Path) -> anyhow::Result<Model> {
-    let file = std::io::BufReader::new(std::fs::File::open(path)?);
-    let line_count = file.lines().count();
-    let file = std::io::BufReader::new(std::fs::File::open(path)?);
-
-    let mut model_config = ModelConfig::default();
-    model_config.parser_config.skip_z3_version_check = true;
-    model_config.parser_config.ignore_invalid_lines = true;
-    model_config.parser_config.show_progress_bar = true;
-    model_config.skip_log_consistency_checks = true;
-    model_config.log_term_equalities = false;
-    model_config.log_internal_term_equalities = false;
-
-    let mut model = Model::new(model_config);
-
-    let e = Error::new(std::io::Error::new(
-        std::io::ErrorKind::InvalidInput,
-        "Invalid path",
-    ));
-
-    let p: Option<String> = match path.to_str() {
-        Some(pa) => Some(pa.to_owned()),
-        None => return Err(e),
-    };
+    /// Only print results: no progress bar, summary, notes or hints (warnings still go to stderr)
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
 
-    model.process(p, file, line_count)?;
-    Ok(model)
-}
+    /// Z3 version that wrote the trace (e.g. 4.12.2), for traces that lack a `[tool-version]` line
+    #[arg(long, value_name = "VERSION")]
+    z3_version: Option<String>,
+
+    /// Seed for the initial GUI layout, so the same input always looks the same
+    #[cfg(feature = "gui")]
+    #[arg(long, value_name = "N")]
+    layout_seed: Option<u64>,
+
+    /// Start the GUI showing only the roots of the graph; click a node to show or hide its successors
+    #[cfg(feature = "gui")]
+    #[arg(long)]
+    collapsed: bool,
+
+    /// Print how long each phase of the analysis took (also on stderr with --verbose)
+    #[arg(long, alias = "timings")]
+    timing: bool,
 
-#[derive(Debug)]
-pub struct InstantiationGraph {
-    pub edges: HashMap<(u64, usize), HashSet<(u64, usize)>>,
-    pub names: HashMap<(u64, usize), String>,
-    pub nodes: HashSet<(u64, usize)>,
+    /// Analyze again whenever a --file trace changes, e.g. while Z3 is still writing it
+    #[arg(long)]
+    watch: bool,
+
+    /// Also dump the raw graph maps (edges, names, nodes)
+    #[arg(long)]
+    debug: bool,
+
+    /// Without a subcommand only the stats (and any requested reports) are printed
+    #[command(subcommand)]
+    cmd: Option<Commands>,
 }
 
-#[derive(Debug)]
-pub struct Profiler {
-    quantifier_stats: Vec<QuantCost>,
-    instantiation_graph: InstantiationGraph,
+fn count_lines(path: &std::path::Path) -> anyhow::Result<usize> {
+    let mut reader = std::io::BufReader::new(File::open(path)?);
+    let mut lines = 0;
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(lines);
+        }
+        lines += buf.iter().filter(|b| **b == b'\n').count();
+        let n = buf.len();
+        reader.consume(n);
+    }
 }
 
-impl Profiler {
-    pub fn parse(filename: &std::path::Path) -> anyhow::Result<Self> {
-        let model = process_file(filename)?;
+/// Parse every `.log` file in `dir`, in name order, printing one CSV row each.
+fn bench(dir: &std::path::Path, options: &ProfilerOptions) -> anyhow::Result<()> {
+    let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|e| e.path()))
+        .collect::<Result<_, _>>()?;
+    paths.retain(|p| p.extension().is_some_and(|ext| ext == "log"));
+    paths.sort();
+
+    println!(
+        "file,bytes,lines,parse_seconds,total_seconds,instantiations,instantiations_per_second"
+    );
+    for path in &paths {
+        let bytes = std::fs::metadata(path)?.len();
+        let lines = count_lines(path)?;
+        let profiler = Profiler::parse_with_options(path, options)?;
+        let total = profiler.timings.total().as_secs_f64();
+        let instantiations = profiler.total_instantiations();
+        let rate = if total > 0. {
+            instantiations as f64 / total
+        } else {
+            0.
+        };
+        println!(
+            "{},{},{},{:.3},{:.3},{},{:.0}",
+            path.display(),
+            bytes,
+            lines,
+            profiler.timings.parse.as_secs_f64(),
+            total,
+            instantiations,
+            rate
+        );
+    }
+    Ok(())
+}
 
-        let graph = Self::make_instantiation_graph(&model);
+/// Waits for `--watch`ed traces to change.
+struct Watch {
+    // Only kept alive, events arrive in `events`
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<notify::Event>>,
+}
 
-        let quant_costs = model.quant_costs();
-        let mut user_quant_costs = quant_costs.into_iter().collect::<Vec<_>>();
-        user_quant_costs.sort_by_key(|v| v.instantiations * v.cost);
-        user_quant_costs.reverse();
+impl Watch {
+    /// How long a trace must stay untouched after a change before it is
+    /// parsed again, so that one long append only causes one parse
+    const DEBOUNCE: Duration = Duration::from_millis(500);
+    /// How often `Watch::wait` checks whether to stop
+    const POLL: Duration = Duration::from_millis(200);
 
-        Ok(Profiler {
-            quantifier_stats: user_quant_costs,
-            instantiation_graph: graph,
+    fn new(files: &[PathBuf]) -> anyhow::Result<Self> {
+        if files.is_empty() || files.iter().any(|f| f.as_os_str() == "-") {
+            anyhow::bail!("--watch needs the traces as --file paths, it cannot watch stdin");
+        }
+        let (tx, events) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(tx)?;
+        for file in files {
+            watcher.watch(file, RecursiveMode::NonRecursive)?;
+        }
+        Ok(Watch {
+            _watcher: watcher,
+            events,
         })
     }
 
-    fn make_instantiation_graph(model: &Model) -> InstantiationGraph {
-        let quantifier_inst_matches =
-            model
-                .instantiations()
-                .iter()
-                .filter(|(_, quant_inst)| match quant_inst.frame {
-                    QiFrame::Discovered { .. } => false,
-                    QiFrame::NewMatch { .. } => true,
-                });
-
-        // Track which instantiations caused which enodes to appear
-        let mut term_blame = HashMap::new();
-        for (qi_key, quant_inst) in quantifier_inst_matches.clone() {
-            for inst in &quant_inst.instances {
-                for node_ident in &inst.enodes {
-                    term_blame.insert(node_ident, qi_key);
+    /// Block until a trace has changed and then stayed untouched for
+    /// `DEBOUNCE`, returning true, or until `stop` is set, returning false.
+    fn wait(&self, stop: &AtomicBool) -> bool {
+        let mut changed = false;
+        loop {
+            let timeout = if changed { Self::DEBOUNCE } else { Self::POLL };
+            match self.events.recv_timeout(timeout) {
+                // Not access events, parsing the trace causes those
+                Ok(Ok(event)) => changed |= event.kind.is_modify() || event.kind.is_create(),
+                Ok(Err(e)) => eprintln!("warning: error while watching the trace: {}", e),
+                Err(RecvTimeoutError::Timeout) if changed => return true,
+                Err(RecvTimeoutError::Timeout) => {
+                    if stop.load(Ordering::Relaxed) {
+                        return false;
+                    }
                 }
+                Err(RecvTimeoutError::Disconnected) => return false,
             }
         }
+    }
+}
 
-        // Create a graph over QuantifierInstances,
-        // where U->V if U produced an e-term that
-        // triggered V
-        let mut graph: BTreeMap<QiKey, BTreeSet<QiKey>> = BTreeMap::new();
-        for (qi_key, _) in quantifier_inst_matches.clone() {
-            graph.insert(*qi_key, BTreeSet::new());
+/// `analyze` again after every change `watch` sees, until `stop` is set. A
+/// failed analysis, e.g. of a trace caught mid-write, is only warned about,
+/// as the next change will retry it. Profiles for the GUI go to `gui`.
+fn reanalyze_on_change(
+    args: &Args,
+    watch: &Watch,
+    stop: &AtomicBool,
+    gui: Option<mpsc::Sender<Profiler>>,
+) {
+    while watch.wait(stop) {
+        if !args.quiet {
+            eprintln!("note: the trace changed, analyzing it again");
         }
-        for (qi_key, quant_inst) in quantifier_inst_matches.clone() {
-            match &quant_inst.frame {
-                QiFrame::Discovered { .. } => {
-                    panic!("We filtered out all of the Discovered instances already!")
-                }
-                QiFrame::NewMatch { used: u, .. } => {
-                    for used in u.iter() {
-                        match used {
-                            MatchedTerm::Trigger(t) => {
-                                match term_blame.get(&t) {
-                                    None => (), //println!("Nobody to blame for {:?}", t),
-                                    Some(qi_responsible) =>
-                                    // Quantifier instantiation that produced the triggering term
-                                    {
-                                        if let Some(resp_edges) = graph.get_mut(&qi_responsible) {
-                                            resp_edges.insert(*qi_key);
-                                        } else {
-                                            panic!("Responsible qikey not found!")
-                                        }
-                                        ()
-                                    }
-                                }
-                            }
-                            MatchedTerm::Equality(_t1, _t2) => (), // TODO: Unclear whether/how to use this case
-                        }
-                    }
+        match analyze(args) {
+            Ok(Some(profiler)) => {
+                // The GUI was closed
+                if gui.as_ref().is_some_and(|gui| gui.send(profiler).is_err()) {
+                    return;
                 }
             }
+            Ok(None) => {}
+            Err(e) => eprintln!(
+                "warning: could not analyze the changed trace ({:#}), will try again on the next change",
+                e
+            ),
         }
-        {
-            let mut edges: HashMap<(u64, usize), HashSet<(u64, usize)>> = HashMap::new();
-            let mut nodes: HashSet<QiKey> = HashSet::new();
-            for (src, tgts) in graph.iter() {
-                nodes.insert(*src);
-                for tgt in tgts {
-                    edges
-                        .entry((src.key, src.version))
-                        .or_insert(std::collections::HashSet::new())
-                        .insert((tgt.key, tgt.version));
-                    nodes.insert(*tgt);
-                }
-            }
-            let names: HashMap<(u64, usize), String> = nodes
-                .iter()
-                .map(|k| {
-                    let ident = model.instantiations().get(&k).unwrap().frame.quantifier();
-                    let name = model.term(ident).expect("not found").name().unwrap();
-                    ((k.key, k.version), name.to_owned())
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    // Before the first parse, so that no change goes unnoticed
+    let watch = if args.watch {
+        Some(Watch::new(&args.file)?)
+    } else {
+        None
+    };
+    let profiler = analyze(&args)?;
+    let Some(profiler) = profiler else {
+        if let Some(watch) = &watch {
+            reanalyze_on_change(&args, watch, &AtomicBool::new(false), None);
+        }
+        return Ok(());
+    };
+
+    #[cfg(feature = "gui")]
+    match watch {
+        Some(watch) => {
+            let (tx, rx) = mpsc::channel();
+            let stop = AtomicBool::new(false);
+            let (args, stop) = (&args, &stop);
+            std::thread::scope(|scope| {
+                scope.spawn(move || reanalyze_on_change(args, &watch, stop, Some(tx)));
+                gui::run(profiler, args.layout_seed, args.collapsed, Some(rx));
+                stop.store(true, Ordering::Relaxed);
+            });
+        }
+        None => gui::run(profiler, args.layout_seed, args.collapsed, None),
+    }
+    #[cfg(not(feature = "gui"))]
+    {
+        drop((profiler, watch));
+        eprintln!("warning: built without the `gui` feature, use the `dot` subcommand to export the graph");
+    }
+    Ok(())
+}
+
+/// Parse the traces and print or write everything `args` asks for. Returns
+/// the profile if the GUI is to be opened on it.
+fn analyze(args: &Args) -> anyhow::Result<Option<Profiler>> {
+    let options = ProfilerOptions {
+        include_discovered: args.include_discovered,
+        trigger_graph: args.trigger_graph,
+        raw_matches: args.raw_matches.is_some(),
+        consistency_checks: args.consistency,
+        check_z3_version: args.check_version,
+        strict_lines: args.strict_lines,
+        log_equalities: args.log_equalities,
+        why_not: args.why_not.clone(),
+        z3_version: args.z3_version.clone(),
+        by_trigger: args.by_trigger.is_some(),
+        edge_terms: args.explain_edges,
+        orphan_report: args.orphan_report,
+        lenient: args.lenient,
+        threads: args.jobs,
+        verbose: args.verbose,
+        quiet: args.quiet,
+    };
+    let load = |path: &std::path::Path| {
+        if Profiler::is_saved_profile(path) {
+            Profiler::load_bin(path)
+        } else {
+            Profiler::parse_with_options(path, &options)
+        }
+    };
+    if let Some(Commands::Bench { dir }) = &args.cmd {
+        bench(dir, &options)?;
+        return Ok(None);
+    }
+    let files = match &args.cache_in {
+        Some(path) => vec![path.clone()],
+        None if args.file.is_empty() => vec!["-".into()],
+        None => args.file.clone(),
+    };
+    if files.len() > MAX_MERGED_TRACES {
+        anyhow::bail!(
+            "{} traces given, but at most {} can be merged",
+            files.len(),
+            MAX_MERGED_TRACES
+        );
+    }
+    let mut profiler = match &args.cache_in {
+        Some(path) => Profiler::load_bin(path)?,
+        None => load(&files[0])?,
+    };
+    for (index, file) in files.iter().enumerate().skip(1) {
+        profiler.merge(load(file)?, index)?;
+    }
+    let mut baseline = match &args.compare {
+        Some(path) => Some(load(path)?),
+        None => None,
+    };
+
+    if args.consistency {
+        profiler.consistency.print();
+    }
+    if profiler.ignored_lines > 0 {
+        eprintln!(
+            "warning: {} trace line(s) could not be parsed and were skipped, the profile is incomplete",
+            profiler.ignored_lines
+        );
+        if !args.quiet {
+            eprintln!("hint: --consistency lists them; --z3-version may help for traces without a version line");
+        }
+    }
+    if let Some(truncation) = &profiler.truncation {
+        eprintln!(
+            "warning: the trace looks cut off ({}), the graph may be missing edges and instantiations",
+            truncation
+        );
+        if !args.quiet {
+            eprintln!("hint: was Z3 killed or did it time out while writing the trace?");
+        }
+    }
+    if let Some(path) = &args.raw_matches {
+        profiler.write_raw_matches(path)?;
+    }
+    if let Some(path) = &args.save_bin {
+        profiler.save_bin(path)?;
+    }
+
+    let mut filter = NameFilter::default();
+    if let Some(path) = &args.ignore_file {
+        filter.ignore = NameFilter::read_patterns(path)?;
+    }
+    if let Some(path) = &args.focus_file {
+        filter.focus = Some(NameFilter::read_patterns(path)?);
+    }
+    if !filter.is_empty() {
+        profiler.retain_quantifiers(|name| filter.allows(name));
+        if let Some(baseline) = &mut baseline {
+            baseline.retain_quantifiers(|name| filter.allows(name));
+        }
+    }
+    // Before ranking and --top, so those only see matching quantifiers
+    if let Some(re) = &args.filter {
+        profiler.retain_quantifiers(|name| re.is_match(name));
+        if let Some(baseline) = &mut baseline {
+            baseline.retain_quantifiers(|name| re.is_match(name));
+        }
+    }
+
+    if args.demangle != Demangle::None {
+        profiler.rename_quantifiers(|name| args.demangle.apply(name));
+        if let Some(baseline) = &mut baseline {
+            baseline.rename_quantifiers(|name| args.demangle.apply(name));
+        }
+    }
+
+    // On the displayed names, as that is what the user sees
+    if let Some(quantifier) = &args.focus {
+        profiler.focus_on(quantifier, false, args.max_depth)?;
+    }
+    if let Some(quantifier) = &args.focus_upstream {
+        profiler.focus_on(quantifier, true, args.max_depth)?;
+    }
+    if let (Some(depth), None, None) = (args.max_depth, &args.focus, &args.focus_upstream) {
+        profiler.keep_within_depth(depth);
+    }
+
+    if args.cost_model != CostModel::Product {
+        let score = args.cost_model.scorer(&profiler.instantiation_graph);
+        profiler.rank_by(score);
+    }
+    if let Some(key) = args.sort {
+        profiler.sort_stats(key);
+    }
+
+    if let Some(n) = args.top {
+        let total = profiler.quantifier_stats.len();
+        if total > n && !args.quiet {
+            eprintln!(
+                "note: keeping the {} highest-ranked of {} quantifiers",
+                n, total
+            );
+        }
+        profiler.keep_top(n);
+    }
+
+    if let Some(k) = args.largest_component {
+        let (kept, total) = profiler.instantiation_graph.largest_components(k);
+        if !args.quiet {
+            eprintln!(
+                "note: keeping the {} largest of {} connected components ({} components and {} nodes dropped)",
+                k.min(total),
+                total,
+                total.saturating_sub(k),
+                profiler.instantiation_graph.nodes.len() - kept.nodes.len(),
+            );
+        }
+        profiler.instantiation_graph = kept;
+    }
+
+    if let Some(window) = args.version_window {
+        profiler.instantiation_graph = profiler.instantiation_graph.version_windows(window);
+    }
+    if args.condense {
+        profiler.instantiation_graph = profiler.instantiation_graph.condensed();
+    }
+
+    if args.aggregate {
+        let graph = profiler.aggregate_by_quantifier();
+        let mut edges: Vec<(u64, &str, &str)> = graph
+            .edges
+            .iter()
+            .flat_map(|(src, tgts)| {
+                tgts.iter().map(|tgt| {
+                    (
+                        graph.weight(*src, *tgt),
+                        &graph.names[src][..],
+                        &graph.names[tgt][..],
+                    )
                 })
-                .collect();
-            let nodes = nodes.into_iter().map(|k| (k.key, k.version)).collect();
+            })
+            .collect();
+        edges.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| (a.1, a.2).cmp(&(b.1, b.2))));
+        println!("Edges between quantifiers, by the number of terms one fed the other:");
+        for (weight, src, tgt) in &edges {
+            println!("{:>10}  {} -> {}", weight, src, tgt);
+        }
+        println!();
+        profiler.instantiation_graph = graph;
+    }
+
+    if args.transitive_reduction {
+        profiler.instantiation_graph = profiler.instantiation_graph.transitive_reduction();
+    }
 
-            InstantiationGraph {
-                edges,
-                names,
-                nodes,
+    // Noted in the DOT export as well, since the file alone looks complete
+    let mut truncation_note = None;
+    if let Some(max) = args.max_edges {
+        let total = profiler.instantiation_graph.edge_count();
+        if total > max {
+            let note = format!(
+                "Only {} of {} edges are shown (--max-edges), the graph is incomplete",
+                max, total
+            );
+            if !args.quiet {
+                eprintln!("note: {}", note);
             }
+            profiler.instantiation_graph = profiler.instantiation_graph.heaviest_edges(max);
+            let graph = &profiler.instantiation_graph;
+            profiler
+                .edge_terms
+                .retain(|(src, tgt), _| graph.edges.get(src).is_some_and(|t| t.contains(tgt)));
+            truncation_note = Some(note);
         }
     }
-    pub fn total_instantiations(&self) -> u64 {
-        self.quantifier_stats
+    if args.debug {
+        // In key order, so that two dumps can be diffed
+        let graph = &profiler.instantiation_graph;
+        let edges: BTreeMap<_, BTreeSet<_>> = graph
+            .edges
             .iter()
-            .fold(0, |acc, cost| acc + cost.instantiations)
+            .map(|(src, tgts)| (src, tgts.iter().collect()))
+            .collect();
+        let names: BTreeMap<_, _> = graph.names.iter().collect();
+        println!("EDGES: ");
+        println!("{:#?}\n\n", edges);
+        println!("NODE NAMES: ");
+        println!("{:#?}\n\n", names);
+        println!("NODES: ");
+        println!("{:#?}", graph.sorted_nodes());
     }
 
-    pub fn print_stats(&self) {
-        for cost in &self.quantifier_stats {
-            let count = cost.instantiations;
-            let msg = format!(
-                "Instantiated {} {} times ({}% of the total) \n",
-                cost.quant,
-                count,
-                100 * count / self.total_instantiations()
+    if let Some(quantifier) = &args.feeders {
+        let Some(feeders) = profiler.feeders(quantifier) else {
+            anyhow::bail!(
+                "No quantifier named `{}` in the instantiation graph",
+                quantifier
             );
-            println!("{}", msg);
+        };
+        println!("Quantifiers feeding {}:", quantifier);
+        for (name, count) in &feeders {
+            println!("  {:>8} edges from {}", count, name);
         }
+        println!();
     }
-}
 
-pub struct BasicApp {
-    g: Graph<NodeData, (), Directed>,
-}
+    if let Some(id) = &args.blame {
+        let Some(chain) = profiler.blame_chain(id) else {
+            anyhow::bail!(
+                "No instantiation produced `{}`: it is from the input problem, or not in the trace",
+                id
+            );
+        };
+        println!("Chain of blame for {}, back to a root:", id);
+        for (i, (key, name)) in chain.iter().enumerate() {
+            let how = if i == 0 {
+                "produced by"
+            } else {
+                "triggered by"
+            };
+            println!("  {} {} ({:#x}/{})", how, name, key.0, key.1);
+        }
+        println!();
+    }
 
-impl BasicApp {
-    fn new(_: &CreationContext<'_>, profiler: Profiler) -> Self {
-        let g = generate_graph(&profiler);
-        Self { g: Graph::from(&g) }
+    for (quantifier, merges) in &profiler.equality_merges {
+        let instantiations = profiler
+            .quantifier_stats
+            .iter()
+            .find(|cost| &cost.quant == quantifier)
+            .map_or(0, |cost| cost.instantiations);
+        if instantiations > 0 && !args.quiet {
+            eprintln!("note: `{}` did fire, {} times", quantifier, instantiations);
+        }
+        println!(
+            "Equality merges involving the trigger terms of {}:",
+            quantifier
+        );
+        if merges.is_empty() {
+            println!("  none: no term headed by one of its trigger symbols was merged");
+        }
+        for merge in merges {
+            println!(
+                "  {} {} -> {} {} ({})",
+                merge.term.id, merge.term.term, merge.into.id, merge.into.term, merge.reason
+            );
+        }
+        println!();
     }
-}
 
-type NodeData = ((u64, usize), String);
+    if args.longest_chain {
+        let graph = &profiler.instantiation_graph;
+        let chain = graph.longest_chain();
+        println!(
+            "Longest instantiation chain: {} instantiations",
+            chain.len()
+        );
+        for key in &chain {
+            println!("  {} ({:#x}/{})", graph.names[key], key.0, key.1);
+        }
+        println!();
+    }
 
-fn generate_graph<'a>(profiler: &'a Profiler) -> StableGraph<NodeData, (), Directed> {
-    let mut g: StableGraph<NodeData, ()> = StableGraph::new();
-    let mut nodes = HashMap::new();
-    for node in &profiler.instantiation_graph.nodes {
-        let name = profiler.instantiation_graph.names.get(node).unwrap().to_owned();
-        let g_node = g.add_node((node.clone(), name));
-        nodes.insert(node.clone(), g_node);
+    if args.roots {
+        println!("Instantiations starting a chain, by instantiations reachable from them:");
+        for (key, name, reachable) in profiler.roots() {
+            println!("{:>10}  {} ({:#x}/{})", reachable, name, key.0, key.1);
+        }
+        println!();
     }
 
-    for (src, dsts) in &profiler.instantiation_graph.edges {
-        let g_src = nodes.get(src).unwrap();
-        for dst in dsts {
-            let g_dst = nodes.get(dst).unwrap();
-            g.add_edge(g_src.clone(), g_dst.clone(), ());
+    if let Some(n) = args.by_trigger {
+        println!("Most matched trigger terms:");
+        println!("{:>10} {:>12}  term", "matches", "quantifiers");
+        for t in profiler.trigger_terms.iter().take(n) {
+            println!("{:>10} {:>12}  {}", t.matches, t.quantifiers, t.term);
         }
+        println!();
     }
-    g
-}
 
-impl App for BasicApp {
-    fn update(&mut self, ctx: &Context, _: &mut eframe::Frame) {
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.add(
-                &mut GraphView::new(&mut self.g).with_interactions(
-                    &SettingsInteraction::default()
-                        .with_dragging_enabled(true)
-                        .with_selection_enabled(true),
-                ).with_custom_node_draw(|ctx, n, state, l| {
-                    let node_centre_loc = n.screen_location(state.meta).to_pos2();
-                                            let rad = n.screen_radius(state.meta, state.style);
-
-                        // first create rect shape
-                        let size = Vec2::new(rad * 1.5, rad * 1.5);
-                        let rect = Rect::from_center_size(node_centre_loc, size);
-                        let shape_rect = Shape::rect_stroke(
-                            rect,
-                            Rounding::default(),
-                            Stroke::new(1., n.color(ctx)),
-                        );
-
-                        // add rect to the layers
-                        l.add(shape_rect);
-
-                        // then create label
-                        let color = ctx.style().visuals.text_color();
-                        let galley = ctx.fonts(|f| {
-                            f.layout_no_wrap(
-                                n.data().unwrap().1.to_owned(),
-                                FontId::new(rad, FontFamily::Monospace),
-                                color,
-                            )
-                        });
-
-                        // we need to offset label by half its size to place it in the center of the rect
-                        let offset = Vec2::new(-galley.size().x / 2., -galley.size().y / 2.);
-
-                        // create the shape and add it to the layers
-                        let shape_label = TextShape::new(node_centre_loc + offset, galley);
-                        l.add(shape_label);
-
-                }),
+    if args.explain_edges {
+        let graph = &profiler.instantiation_graph;
+        println!("Terms behind each edge:");
+        for ((src, tgt), terms) in &profiler.edge_terms {
+            // Edges to nodes dropped by the filters above
+            if !graph.edges.get(src).is_some_and(|tgts| tgts.contains(tgt)) {
+                continue;
+            }
+            println!(
+                "  {} ({:#x}/{}) -> {} ({:#x}/{})",
+                graph.names[src], src.0, src.1, graph.names[tgt], tgt.0, tgt.1
             );
-        });
+            for t in terms {
+                println!("      {}  {}", t.id, t.term);
+            }
+        }
+        println!();
     }
-}
 
-fn main() -> anyhow::Result<()> {
-    let args = Args::parse();
-    let profiler = Profiler::parse(&args.file)?;
-    println!("EDGES: ");
-    println!("{:?}\n\n", profiler.instantiation_graph.edges);
-    println!("NODE NAMES: ");
-    println!("{:?}\n\n", profiler.instantiation_graph.names);
-    println!("NODES: ");
-    println!("{:?}", profiler.instantiation_graph.nodes);
-    profiler.print_stats();
-
-    if let Commands::Dot = args.cmd {
-        let graph = generate_graph(&profiler);
-        let graph_data = Dot::with_config(&graph, &[]);
-        let txt = format!("{:?}", graph_data);
+    if args.orphan_report {
+        println!(
+            "Matches on terms no instantiation produced (input-driven rather than cascading):"
+        );
+        println!(
+            "{:>10} {:>10} {:>8} {:>12}  quantifier",
+            "matches", "orphans", "ratio", "orphan terms"
+        );
+        for o in &profiler.orphans {
+            println!(
+                "{:>10} {:>10} {:>7.1}% {:>12}  {}",
+                o.matches,
+                o.orphan_matches,
+                percentage(o.orphan_matches, o.matches),
+                o.orphan_terms,
+                o.quantifier
+            );
+        }
+        println!();
+    }
+
+    if args.discovered {
+        let discovered: u64 = profiler.discovered.iter().map(|d| d.instantiations).sum();
+        println!(
+            "Instantiations from theory reasoning or MBQI rather than E-matching: {} ({:.1}% of the total)",
+            discovered,
+            percentage(discovered, profiler.total_instantiations())
+        );
+        if !profiler.discovered.is_empty() {
+            println!("{:>10} {:>8}  quantifier", "discovered", "share");
+        }
+        for d in &profiler.discovered {
+            let all = profiler
+                .quantifier_stats
+                .iter()
+                .find(|cost| cost.quant == d.quantifier)
+                .map_or(0, |cost| cost.instantiations);
+            println!(
+                "{:>10} {:>7.1}%  {}",
+                d.instantiations,
+                percentage(d.instantiations, all),
+                d.quantifier
+            );
+        }
+        println!();
+    }
+
+    if args.self_triggering {
+        let quantifiers = profiler.instantiation_graph.self_triggering();
+        println!("Self-triggering quantifiers: {}", quantifiers.len());
+        for (name, edges) in &quantifiers {
+            println!("  {:>8} edges from {} to itself", edges, name);
+        }
+        println!();
+    }
+
+    if args.centrality {
+        let nodes = profiler.instantiation_graph.nodes.len();
+        let (measure, ranked) = profiler.centrality(args.betweenness_max_nodes);
+        if measure != Centrality::Betweenness {
+            eprintln!(
+                "warning: the graph has {} nodes, more than --betweenness-max-nodes {}; ranking by degree centrality instead",
+                nodes, args.betweenness_max_nodes
+            );
+        }
+        println!("Quantifiers by {} centrality:", measure.label());
+        for (name, score) in &ranked {
+            println!("  {:>12.1}  {}", score, name);
+        }
+        println!();
+    }
+
+    if args.depth_histogram {
+        // Deeper instantiations share the last column
+        const COLUMNS: usize = 10;
+        let histograms = profiler.depth_histograms();
+        let mut header = String::new();
+        for depth in 0..COLUMNS - 1 {
+            header.push_str(&format!("{:>7}", depth));
+        }
+        header.push_str(&format!("{:>7}", format!("{}+", COLUMNS - 1)));
+        println!("Instantiations by depth (edges from the nearest root):");
+        println!("{} {:>9}  quantifier", header, "no root");
+        for h in &histograms {
+            let mut row = String::new();
+            for depth in 0..COLUMNS - 1 {
+                row.push_str(&format!("{:>7}", h.counts.get(depth).copied().unwrap_or(0)));
+            }
+            let deeper: u64 = h.counts.iter().skip(COLUMNS - 1).sum();
+            row.push_str(&format!("{:>7}", deeper));
+            println!("{} {:>9}  {}", row, h.unreachable, h.quantifier);
+        }
+        println!();
+    }
+
+    if args.loops {
+        let loops = profiler.matching_loops(args.loops_ignore_discovered);
+        println!("Matching loops: {}", loops.len());
+        for l in &loops {
+            let mut cycle = l.cycle.clone();
+            cycle.extend(l.cycle.first().cloned());
+            println!(
+                "  {} instantiations: {}",
+                l.instantiations.len(),
+                cycle.join(" -> ")
+            );
+        }
+        println!();
+    }
+
+    if args.advise {
+        println!("Suggestions (heuristic, double-check before acting on them):");
+        for advice in profiler.advise(args.loops_ignore_discovered) {
+            println!("  - {}", advice);
+        }
+        println!();
+    }
+
+    if let (Some(baseline), Some(path)) = (&baseline, &args.compare) {
+        let threshold = DiffThreshold {
+            min_delta: args.diff_threshold,
+            min_delta_pct: args.diff_threshold_pct,
+        };
+        println!(
+            "Instantiation changes from {} to {}:",
+            path.display(),
+            files
+                .iter()
+                .map(|f| f.display().to_string())
+                .collect::<Vec<_>>()
+                .join(" + ")
+        );
+        print!("{}", profiler.render_comparison(baseline, &threshold));
+        println!();
+    }
+
+    if let Some(path) = &args.json {
+        profiler.write_json(path)?;
+    }
+    if let Some(path) = &args.csv {
+        std::fs::write(path, profiler.render_csv())?;
+    }
+
+    match args.format {
+        OutputFormat::Text => {
+            if !args.quiet {
+                profiler.print_summary();
+            }
+            profiler.print_stats(args.lifespan);
+        }
+        OutputFormat::Table => {
+            if !args.quiet {
+                profiler.print_summary();
+            }
+            print!("{}", profiler.render_table(args.lifespan));
+        }
+        OutputFormat::Markdown => print!("{}", profiler.render_markdown(args.lifespan)),
+    }
+    if args.timing {
+        profiler.timings.print();
+    } else if args.verbose {
+        eprint!("{}", profiler.timings.render());
+    }
+
+    if let Some(path) = &args.graphml {
+        std::fs::write(path, profiler.render_graphml())?;
+    }
+    if let Some(path) = &args.graph_json {
+        match &profiler.trigger_graph {
+            Some(trigger_graph) => trigger_graph.write_json(path)?,
+            None => profiler.write_graph_json(path)?,
+        }
+    }
+    if let Some(path) = &args.svg {
+        std::fs::write(path, profiler.render_svg())?;
+    }
+
+    if let Some(Commands::Dot) = args.cmd {
+        let txt = match &profiler.trigger_graph {
+            Some(trigger_graph) => render_trigger_dot(trigger_graph),
+            None if args.dot_ranks => ranked_dot(&profiler.instantiation_graph),
+            None => render_dot(&profiler.instantiation_graph),
+        };
         let mut file = File::create("graph.dot")?;
+        if let (Some(note), None) = (&truncation_note, &profiler.trigger_graph) {
+            writeln!(file, "// {}", note)?;
+        }
         file.write_all(txt.as_bytes())?;
-        return Ok(());
+        return Ok(None);
+    }
+    if !matches!(args.cmd, Some(Commands::Gui)) {
+        return Ok(None);
     }
 
-    let native_options = eframe::NativeOptions::default();
-    run_native(
-        "SMT quantifier instantiations graph",
-        native_options,
-        Box::new(|cc| Box::new(BasicApp::new(cc, profiler))),
-    )
-    .unwrap();
-    Ok(())
+    if args.trigger_graph && !args.quiet {
+        eprintln!("note: --trigger-graph only affects the `dot` export, the GUI shows the quantifier graph");
+    }
+    Ok(Some(profiler))
 }
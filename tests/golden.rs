@@ -0,0 +1,670 @@
+//! Parse the small traces in `tests/fixtures` and compare the results against
+//! the checked-in expectations. Run with `UPDATE_GOLDEN=1` to rewrite the
+//! `.graph`, `.dot`, `.json` and `.txt` files after an intended change.
+
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use gordian_profiler::{
+    render_dot, render_trigger_dot, Demangle, DiffThreshold, InstantiationGraph, MatchingLoop,
+    NameFilter, OrphanCount, Profiler, ProfilerOptions, QuantifierDelta, SortKey, TriggerGraph,
+};
+use z3tracer::model::QuantCost;
+
+fn fixture_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn parse_fixture(name: &str) -> Profiler {
+    let file = File::open(fixture_path(name)).expect("fixture exists");
+    Profiler::parse_reader(BufReader::new(file), None).expect("fixture parses")
+}
+
+/// Nodes and edges in key order, so the output does not depend on hash order.
+fn serialize(graph: &InstantiationGraph) -> String {
+    let mut out = String::new();
+    let mut nodes: Vec<&(u64, usize)> = graph.nodes.iter().collect();
+    nodes.sort();
+    for node in nodes {
+        out.push_str(&format!(
+            "node {:#x}/{} {}\n",
+            node.0, node.1, graph.names[node]
+        ));
+    }
+    let mut edges: Vec<((u64, usize), (u64, usize))> = graph
+        .edges
+        .iter()
+        .flat_map(|(src, tgts)| tgts.iter().map(move |tgt| (*src, *tgt)))
+        .collect();
+    edges.sort();
+    for (src, tgt) in edges {
+        out.push_str(&format!(
+            "edge {:#x}/{} -> {:#x}/{}\n",
+            src.0, src.1, tgt.0, tgt.1
+        ));
+    }
+    out
+}
+
+fn assert_golden(name: &str, actual: &str) {
+    let path = fixture_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, actual).expect("golden file is writable");
+        return;
+    }
+    let expected = std::fs::read_to_string(&path).expect("golden file exists");
+    assert_eq!(actual, expected, "{} is out of date", path.display());
+}
+
+fn stats(profiler: &Profiler) -> Vec<(&str, u64)> {
+    profiler
+        .quantifier_stats
+        .iter()
+        .map(|cost| (cost.quant.as_str(), cost.instantiations))
+        .collect()
+}
+
+#[test]
+fn matching_loop() {
+    let profiler = parse_fixture("matching_loop.log");
+    assert_eq!(stats(&profiler), vec![("loop", 3), ("other", 1)]);
+    assert_eq!(profiler.total_instantiations(), 4);
+    assert_eq!(profiler.roots(), vec![((0x10, 0), "loop".to_string(), 2)]);
+    // Two enodes per `loop` instance, `p(a)` for `other`
+    assert_eq!(
+        (profiler.enodes_of("loop"), profiler.enodes_of("other")),
+        (6, 1)
+    );
+    assert_golden(
+        "matching_loop.graph",
+        &serialize(&profiler.instantiation_graph),
+    );
+}
+
+#[test]
+fn csv_export() {
+    let mut profiler = parse_fixture("matching_loop.log");
+    profiler.rename_quantifiers(|name| match name {
+        "other" => "say \"hi\", then".to_owned(),
+        _ => name.to_owned(),
+    });
+    let csv = profiler.render_csv();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(
+        lines[0],
+        "quantifier,instantiations,cost,percentage,in_degree,out_degree,enodes"
+    );
+    assert!(lines[1].starts_with("loop,3,"));
+    assert!(lines[2].starts_with("\"say \"\"hi\"\", then\",1,"));
+}
+
+#[test]
+fn edge_terms() {
+    let options = ProfilerOptions {
+        edge_terms: true,
+        ..ProfilerOptions::default()
+    };
+    let file = File::open(fixture_path("matching_loop.log")).expect("fixture exists");
+    let profiler = Profiler::parse_reader_with_options(BufReader::new(file), None, &options)
+        .expect("fixture parses");
+    let edges: Vec<_> = profiler.edge_terms.keys().copied().collect();
+    assert_eq!(edges, vec![((0x10, 0), (0x20, 0)), ((0x20, 0), (0x30, 0))]);
+    // `0x20` matched `f(g(a))`, which `0x10` produced
+    let terms = &profiler.edge_terms[&((0x10, 0), (0x20, 0))];
+    assert_eq!(terms.len(), 1);
+    assert_eq!(terms[0].id, "#16");
+    assert!(terms[0].term.contains('g'));
+}
+
+#[test]
+fn orphan_matches() {
+    let options = ProfilerOptions {
+        orphan_report: true,
+        ..ProfilerOptions::default()
+    };
+    let file = File::open(fixture_path("matching_loop.log")).expect("fixture exists");
+    let profiler = Profiler::parse_reader_with_options(BufReader::new(file), None, &options)
+        .expect("fixture parses");
+    let orphan = |quantifier: &str, matches, orphans| OrphanCount {
+        quantifier: quantifier.to_owned(),
+        matches,
+        orphan_matches: orphans,
+        orphan_terms: orphans,
+    };
+    // `f(a)` and `h(a)` are input terms; the later `loop` instances matched
+    // terms the earlier ones produced
+    assert_eq!(
+        profiler.orphans,
+        vec![orphan("loop", 3, 1), orphan("other", 1, 1)]
+    );
+}
+
+#[test]
+fn blame_chain() {
+    let profiler = parse_fixture("matching_loop.log");
+    let keys = |chain: Vec<((u64, usize), String)>| -> Vec<u64> {
+        chain.into_iter().map(|(key, _)| key.0).collect()
+    };
+    // `f(g(g(g(a))))` came from the third `loop` instance, which the other two led to
+    assert_eq!(
+        profiler.blame_chain("#20").map(keys),
+        Some(vec![0x30, 0x20, 0x10])
+    );
+    assert_eq!(profiler.blame_chain("21").map(keys), Some(vec![0x40]));
+    // `a` is from the input problem
+    assert_eq!(profiler.blame_chain("#12"), None);
+}
+
+#[test]
+fn sort_by_name() {
+    let mut profiler = parse_fixture("equality_match.log");
+    profiler.sort_stats(SortKey::Name);
+    assert_eq!(stats(&profiler), vec![("consumer", 1), ("producer", 1)]);
+}
+
+#[test]
+fn graph_export() {
+    let profiler = parse_fixture("matching_loop.log");
+    let export = profiler.graph_export();
+    let nodes: Vec<(&str, &str, u64)> = export
+        .nodes
+        .iter()
+        .map(|n| (n.id.as_str(), n.name.as_str(), n.instantiations))
+        .collect();
+    assert_eq!(
+        nodes,
+        vec![
+            ("n10_0", "loop", 3),
+            ("n20_0", "loop", 3),
+            ("n30_0", "loop", 3),
+            ("n40_0", "other", 1)
+        ]
+    );
+    let edges: Vec<(&str, &str, u64)> = export
+        .edges
+        .iter()
+        .map(|e| (e.source.as_str(), e.target.as_str(), e.weight))
+        .collect();
+    assert_eq!(edges, vec![("n10_0", "n20_0", 1), ("n20_0", "n30_0", 1)]);
+}
+
+#[test]
+fn dot_and_json_exports() {
+    let profiler = parse_fixture("matching_loop.log");
+    assert_golden(
+        "matching_loop.dot",
+        &render_dot(&profiler.instantiation_graph),
+    );
+    let json = serde_json::to_string_pretty(&profiler.graph_export()).expect("export serializes");
+    assert_golden("matching_loop.json", &format!("{}\n", json));
+}
+
+fn parse_trigger_graph(name: &str) -> TriggerGraph {
+    let options = ProfilerOptions {
+        trigger_graph: true,
+        ..ProfilerOptions::default()
+    };
+    let file = File::open(fixture_path(name)).expect("fixture exists");
+    let profiler = Profiler::parse_reader_with_options(BufReader::new(file), None, &options)
+        .expect("fixture parses");
+    profiler.trigger_graph.expect("graph was built")
+}
+
+#[test]
+fn trigger_graph_dot() {
+    let dot = render_trigger_dot(&parse_trigger_graph("matching_loop.log"));
+    assert!(dot.starts_with("digraph {\n    n10_0 [ label = \"loop\" ]\n"));
+    // `0x20` matched a term of `0x10` through the pattern `#3` of `loop`
+    assert!(dot.contains("\n    n10_0 -> \"p#3\"\n"));
+    assert!(dot.contains("\n    \"p#3\" -> n20_0\n"));
+    assert!(dot.contains("\n    \"p#9\" -> n40_0\n"));
+}
+
+#[test]
+fn trigger_graph_json() {
+    let export = parse_trigger_graph("matching_loop.log").export();
+    let nodes: Vec<(&str, &str)> = export
+        .nodes
+        .iter()
+        .map(|n| (n.id.as_str(), n.kind))
+        .collect();
+    assert_eq!(
+        nodes,
+        vec![
+            ("n10_0", "instantiation"),
+            ("n20_0", "instantiation"),
+            ("n30_0", "instantiation"),
+            ("n40_0", "instantiation"),
+            ("p#3", "trigger"),
+            ("p#9", "trigger"),
+        ]
+    );
+    assert!(export.nodes[4].name.starts_with("loop "));
+    let edges: Vec<(&str, &str)> = export
+        .edges
+        .iter()
+        .map(|e| (e.source.as_str(), e.target.as_str()))
+        .collect();
+    assert_eq!(
+        edges,
+        vec![
+            ("n10_0", "p#3"),
+            ("n20_0", "p#3"),
+            ("p#3", "n10_0"),
+            ("p#3", "n20_0"),
+            ("p#3", "n30_0"),
+            ("p#9", "n40_0"),
+        ]
+    );
+    let json = serde_json::to_string(&export).expect("export serializes");
+    assert!(json.contains(r#"{"id":"p#9","name":"other "#));
+    assert!(json.contains(r#"{"source":"p#9","target":"n40_0"}"#));
+}
+
+#[test]
+fn svg_export() {
+    let mut profiler = parse_fixture("matching_loop.log");
+    profiler.rename_quantifiers(|name| match name {
+        "other" => "a<b".to_owned(),
+        _ => name.to_owned(),
+    });
+    let svg = profiler.render_svg();
+    assert!(svg.starts_with("<svg "));
+    assert!(svg.ends_with("</svg>\n"));
+    assert_eq!(svg.matches("<rect ").count(), 4);
+    assert_eq!(svg.matches("<line ").count(), 2);
+    assert!(svg.contains("<title>a&lt;b</title>"));
+    assert!(!svg.contains("a<b"));
+}
+
+/// The fixture's profile with the given instantiation counts instead.
+fn with_counts(counts: &[(&str, u64)]) -> Profiler {
+    let mut profiler = parse_fixture("matching_loop.log");
+    profiler.quantifier_stats = counts
+        .iter()
+        .map(|(quant, instantiations)| QuantCost {
+            quant: quant.to_string(),
+            instantiations: *instantiations,
+            cost: *instantiations,
+        })
+        .collect();
+    profiler
+}
+
+/// The fixture's profile with a graph of nodes `(i, 0)` named after
+/// `names[i]` and the given edges instead.
+fn with_graph(names: &[&str], edges: &[(u64, u64)]) -> Profiler {
+    let mut profiler = parse_fixture("matching_loop.log");
+    let mut graph = InstantiationGraph::default();
+    for (i, name) in names.iter().enumerate() {
+        graph.nodes.insert((i as u64, 0));
+        graph.names.insert((i as u64, 0), name.to_string());
+    }
+    for (src, tgt) in edges {
+        graph.edges.entry((*src, 0)).or_default().insert((*tgt, 0));
+    }
+    profiler.instantiation_graph = graph;
+    profiler
+}
+
+#[test]
+fn self_loop_is_a_matching_loop() {
+    let profiler = with_graph(&["a", "b", "c"], &[(0, 0), (1, 2)]);
+    assert_eq!(
+        profiler.matching_loops(false),
+        vec![MatchingLoop {
+            instantiations: vec![(0, 0)],
+            cycle: vec!["a".to_owned()],
+        }]
+    );
+}
+
+#[test]
+fn roots_by_reachable_instantiations() {
+    // `b` reaches three other instantiations, `a` two and `f` one
+    let profiler = with_graph(
+        &["a", "x", "b", "y", "z", "w", "f", "v"],
+        &[(0, 1), (2, 3), (3, 4), (3, 5), (6, 7), (1, 4)],
+    );
+    let roots: Vec<(u64, String, usize)> = profiler
+        .roots()
+        .into_iter()
+        .map(|(key, name, reachable)| (key.0, name, reachable))
+        .collect();
+    assert_eq!(
+        roots,
+        vec![
+            (2, "b".to_owned(), 3),
+            (0, "a".to_owned(), 2),
+            (6, "f".to_owned(), 1),
+        ]
+    );
+}
+
+#[test]
+fn top_keeps_direct_neighbours() {
+    let mut profiler = with_graph(
+        &["u", "x", "t", "y", "v", "z", "w"],
+        &[(0, 1), (1, 2), (2, 3), (3, 4), (5, 6)],
+    );
+    profiler.quantifier_stats =
+        with_counts(&[("t", 10), ("x", 5), ("y", 5), ("u", 1)]).quantifier_stats;
+    profiler.keep_top(1);
+    assert_eq!(stats(&profiler), vec![("t", 10)]);
+    let graph = &profiler.instantiation_graph;
+    let mut nodes: Vec<&str> = graph
+        .nodes
+        .iter()
+        .map(|k| graph.names[k].as_str())
+        .collect();
+    nodes.sort();
+    assert_eq!(nodes, ["t", "x", "y"]);
+    assert!(graph.edges[&(1, 0)].contains(&(2, 0)));
+    assert!(graph.edges[&(2, 0)].contains(&(3, 0)));
+    assert!(!graph.edges.contains_key(&(3, 0)));
+}
+
+#[test]
+fn comparison_counts_missing_quantifiers_as_zero() {
+    let before = with_counts(&[("a", 5), ("b", 2)]);
+    let after = with_counts(&[("a", 6), ("c", 3)]);
+    let delta = |quant: &str, before, after| QuantifierDelta {
+        quant: quant.to_owned(),
+        before,
+        after,
+    };
+    assert_eq!(
+        after.compare(&before),
+        vec![delta("c", 0, 3), delta("b", 2, 0), delta("a", 5, 6)]
+    );
+}
+
+#[test]
+fn comparison_hides_small_changes() {
+    let before = with_counts(&[("a", 100), ("b", 10), ("c", 50)]);
+    let after = with_counts(&[("a", 110), ("b", 12), ("c", 50), ("d", 4)]);
+    // `a` changed by enough instantiations but only 10%, `b` by too few
+    let threshold = DiffThreshold {
+        min_delta: 3,
+        min_delta_pct: 20.0,
+    };
+    assert_golden(
+        "compare_thresholds.txt",
+        &after.render_comparison(&before, &threshold),
+    );
+    let threshold = DiffThreshold {
+        min_delta: 3,
+        min_delta_pct: 0.0,
+    };
+    let table = after.render_comparison(&before, &threshold);
+    assert!(table.contains("  a\n") && table.contains("  d\n"));
+    assert!(!table.contains("  b\n"));
+    assert!(table.ends_with("(2 quantifiers below the diff threshold not shown)\n"));
+}
+
+#[test]
+fn filter_matches_raw_names() {
+    let mut profiler = with_counts(&[
+        ("_module.__default.Foo_0123abcd", 3),
+        ("_module.__default.Foo_4567cdef", 2),
+        ("Bar", 1),
+    ]);
+    // As `--filter` does, on the names in the trace and before `--demangle`
+    let re = regex::Regex::new("^_module\\.__default\\.Foo_0123abcd$").unwrap();
+    profiler.retain_quantifiers(|name| re.is_match(name));
+    profiler.rename_quantifiers(|name| Demangle::Dafny.apply(name));
+    assert_eq!(stats(&profiler), vec![("Foo", 3)]);
+    assert_eq!(
+        profiler.raw_names["Foo"].iter().collect::<Vec<_>>(),
+        ["_module.__default.Foo_0123abcd"]
+    );
+}
+
+#[test]
+fn table_footnotes_long_names() {
+    let mut profiler = parse_fixture("matching_loop.log");
+    let long = "x".repeat(60);
+    profiler.rename_quantifiers(|name| match name {
+        "other" => long.clone(),
+        _ => name.to_owned(),
+    });
+    let table = profiler.render_table(false);
+    let lines: Vec<&str> = table.lines().collect();
+    assert!(lines[0].starts_with(" Rank  Quantifier"));
+    assert!(lines[1].starts_with("    1  loop "));
+    assert!(lines[2].starts_with("    2  xxx"));
+    assert!(lines[2].contains("… [1]"));
+    assert_eq!(lines.last(), Some(&format!("[1] {}", long).as_str()));
+    // The numbers line up under their headers
+    assert_eq!(lines[0].len(), lines[1].len());
+}
+
+#[test]
+fn gzipped_trace() {
+    let plain = parse_fixture("matching_loop.log");
+    let gzipped = Profiler::parse(&fixture_path("matching_loop.log.gz")).expect("fixture parses");
+    assert_eq!(stats(&gzipped), stats(&plain));
+    assert_eq!(
+        serialize(&gzipped.instantiation_graph),
+        serialize(&plain.instantiation_graph)
+    );
+}
+
+#[test]
+fn parallel_matches_serial() {
+    for name in ["matching_loop.log", "equality_match.log"] {
+        let parse = |threads| {
+            let options = ProfilerOptions {
+                threads: Some(threads),
+                ..ProfilerOptions::default()
+            };
+            let file = File::open(fixture_path(name)).expect("fixture exists");
+            Profiler::parse_reader_with_options(BufReader::new(file), None, &options)
+                .expect("fixture parses")
+        };
+        let serial = parse(1);
+        let parallel = parse(4);
+        assert_eq!(stats(&parallel), stats(&serial));
+        assert_eq!(
+            serialize(&parallel.instantiation_graph),
+            serialize(&serial.instantiation_graph)
+        );
+        assert_eq!(
+            parallel.instantiation_graph.weights,
+            serial.instantiation_graph.weights
+        );
+    }
+}
+
+#[test]
+fn saved_profile_round_trip() {
+    let profiler = parse_fixture("matching_loop.log");
+    let path = std::env::temp_dir().join(format!("gordian-{}.profile", std::process::id()));
+    profiler.save_bin(&path).expect("profile is writable");
+    assert!(Profiler::is_saved_profile(&path));
+    let loaded = Profiler::load_bin(&path).expect("profile loads");
+    assert_eq!(stats(&loaded), stats(&profiler));
+    assert_eq!(
+        serialize(&loaded.instantiation_graph),
+        serialize(&profiler.instantiation_graph)
+    );
+    assert_eq!(loaded.enodes_of("loop"), 6);
+
+    // A different build string right after the format version
+    let mut bytes = std::fs::read(&path).unwrap();
+    let dot = bytes
+        .iter()
+        .position(|&b| b == b'.')
+        .expect("version in header");
+    bytes[dot] = b'!';
+    std::fs::write(&path, bytes).unwrap();
+    let err = Profiler::load_bin(&path).expect_err("other builds are rejected");
+    std::fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains("re-create it from the trace"));
+}
+
+#[test]
+fn ignore_file_excludes_from_every_report() {
+    let path = std::env::temp_dir().join(format!("gordian-{}.ignore", std::process::id()));
+    std::fs::write(&path, "# the quantifier `other`\n\not*er\n").unwrap();
+    let filter = NameFilter {
+        ignore: NameFilter::read_patterns(&path).unwrap(),
+        focus: None,
+    };
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(filter.ignore, vec!["ot*er"]);
+
+    let options = ProfilerOptions {
+        orphan_report: true,
+        by_trigger: true,
+        raw_matches: true,
+        ..ProfilerOptions::default()
+    };
+    let file = File::open(fixture_path("matching_loop.log")).expect("fixture exists");
+    let mut profiler = Profiler::parse_reader_with_options(BufReader::new(file), None, &options)
+        .expect("fixture parses");
+    assert!(profiler.orphans.iter().any(|o| o.quantifier == "other"));
+    profiler.retain_quantifiers(|name| filter.allows(name));
+
+    assert_eq!(stats(&profiler), vec![("loop", 3)]);
+    assert_eq!(profiler.enodes_of("other"), 0);
+    assert!(!profiler.lifespans.contains_key("other"));
+    assert!(profiler.orphans.iter().all(|o| o.quantifier != "other"));
+    assert!(profiler.raw_matches.iter().all(|m| m.quantifier != "other"));
+    // `h(a)` was only matched by `other`, the three `f` terms by `loop`
+    assert_eq!(profiler.trigger_terms.len(), 3);
+    assert!(profiler
+        .trigger_terms
+        .iter()
+        .all(|t| t.per_quantifier.keys().all(|q| q == "loop")));
+    assert!(profiler
+        .instantiation_graph
+        .names
+        .values()
+        .all(|name| name != "other"));
+    for report in [
+        profiler.render_stats(true),
+        profiler.render_table(true),
+        profiler.render_markdown(true),
+        profiler.render_csv(),
+    ] {
+        assert!(!report.contains("other"), "{}", report);
+    }
+}
+
+#[test]
+fn merged_traces() {
+    let mut profiler = parse_fixture("matching_loop.log");
+    profiler
+        .merge(parse_fixture("matching_loop.log"), 1)
+        .expect("keys fit");
+    assert_eq!(stats(&profiler), vec![("loop", 6), ("other", 2)]);
+    assert_eq!(profiler.enodes_of("loop"), 12);
+    let graph = &profiler.instantiation_graph;
+    assert_eq!(graph.nodes.len(), 8);
+    assert_eq!(graph.edge_count(), 4);
+    let copy = (0x20 | 1 << 56, 0);
+    assert!(graph.edges[&(0x10 | 1 << 56, 0)].contains(&copy));
+    assert!(!graph.edges[&(0x10, 0)].contains(&copy));
+    // The top byte is the trace's index, so there is no room for more
+    let err = profiler
+        .merge(parse_fixture("matching_loop.log"), 256)
+        .unwrap_err();
+    assert!(err.to_string().contains("more than 256 traces"));
+    // Nor for keys that already use it
+    let mut high = parse_fixture("matching_loop.log");
+    high.instantiation_graph = high.instantiation_graph.namespaced(1 << 60);
+    let err = profiler.merge(high, 2).unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("key 0x1000000000000010 is too large"));
+}
+
+#[test]
+fn equality_only_match() {
+    let profiler = parse_fixture("equality_match.log");
+    let mut stats = stats(&profiler);
+    stats.sort();
+    assert_eq!(stats, vec![("consumer", 1), ("producer", 1)]);
+    assert_eq!(profiler.total_instantiations(), 2);
+    // `consumer` only matched through `h(b) = g(a)`, and `g(a)` came from `producer`
+    assert!(profiler.instantiation_graph.edges[&(0x10, 0)].contains(&(0x20, 0)));
+    assert_golden(
+        "equality_match.graph",
+        &serialize(&profiler.instantiation_graph),
+    );
+}
+
+#[test]
+fn equality_merges_of_unfired_quantifier() {
+    let options = ProfilerOptions {
+        why_not: vec!["consumer".to_owned()],
+        ..ProfilerOptions::default()
+    };
+    let parse = |options: &ProfilerOptions| {
+        let file = File::open(fixture_path("suppressed_match.log")).expect("fixture exists");
+        Profiler::parse_reader_with_options(BufReader::new(file), None, options)
+    };
+    let err = parse(&options).expect_err("needs the equalities");
+    assert!(err.to_string().contains("--log-equalities"));
+
+    let options = ProfilerOptions {
+        log_equalities: true,
+        ..options
+    };
+    let profiler = parse(&options).expect("fixture parses");
+    assert_eq!(stats(&profiler), vec![("producer", 1)]);
+    // `h(b)`, which `consumer` would have matched, went into the class of `g(a)`
+    let merges = &profiler.equality_merges["consumer"];
+    assert_eq!(merges.len(), 1);
+    assert_eq!(
+        (merges[0].term.id.as_str(), merges[0].into.id.as_str()),
+        ("#14", "#12")
+    );
+    assert_eq!(merges[0].reason, "lit #15");
+}
+
+#[test]
+fn anonymous_quantifier() {
+    let profiler = parse_fixture("anonymous.log");
+    assert_eq!(stats(&profiler), vec![("<anon:#5>", 1)]);
+    assert_eq!(profiler.anonymous_quantifiers, 1);
+    assert_eq!(profiler.instantiation_graph.names[&(0x10, 0)], "<anon:#5>");
+}
+
+#[test]
+fn truncated_trace() {
+    let profiler = parse_fixture("truncated.log");
+    let truncation = profiler.truncation.expect("truncation is noticed");
+    assert!(truncation.contains("last line is incomplete"));
+    assert!(truncation.contains("1 of 1 instantiations"));
+    assert!(parse_fixture("matching_loop.log").truncation.is_none());
+}
+
+#[test]
+fn no_instantiations() {
+    let profiler = parse_fixture("no_instances.log");
+    assert_eq!(profiler.total_instantiations(), 0);
+    assert!(profiler.instantiation_graph.nodes.is_empty());
+    // Must not divide by the zero total.
+    let summary = profiler.render_summary();
+    assert!(summary.contains("Instantiations: 0\n"), "{}", summary);
+    assert!(summary.contains("Cycles:         no\n"), "{}", summary);
+    assert_eq!(
+        profiler.render_stats(false),
+        "No quantifier instantiations found\n"
+    );
+    assert_eq!(profiler.render_table(false).lines().count(), 1);
+    assert_eq!(
+        profiler.render_csv(),
+        "quantifier,instantiations,cost,percentage,in_degree,out_degree,enodes\n"
+    );
+    assert!(profiler
+        .render_markdown(false)
+        .starts_with("| Quantifier |"));
+}
@@ -0,0 +1,344 @@
+//! Analyses on small hand-made instantiation graphs.
+
+use std::collections::BTreeMap;
+
+use gordian_profiler::InstantiationGraph;
+
+/// A graph with nodes `(i, 0)` named after `names[i]` and the given edges.
+fn graph(names: &[&str], edges: &[(u64, u64)]) -> InstantiationGraph {
+    let mut graph = InstantiationGraph::default();
+    for (i, name) in names.iter().enumerate() {
+        graph.nodes.insert((i as u64, 0));
+        graph.names.insert((i as u64, 0), name.to_string());
+    }
+    for (src, tgt) in edges {
+        graph.edges.entry((*src, 0)).or_default().insert((*tgt, 0));
+    }
+    graph
+}
+
+fn chain_names(graph: &InstantiationGraph) -> Vec<&str> {
+    graph
+        .longest_chain()
+        .iter()
+        .map(|k| graph.names[k].as_str())
+        .collect()
+}
+
+#[test]
+fn longest_chain_of_linear_graph() {
+    let g = graph(&["a", "b", "c", "d"], &[(0, 1), (1, 2)]);
+    assert_eq!(chain_names(&g), vec!["a", "b", "c"]);
+}
+
+#[test]
+fn longest_chain_counts_a_cycle_once() {
+    let g = graph(&["a", "b", "c"], &[(0, 1), (1, 0), (1, 2), (2, 2)]);
+    assert_eq!(g.longest_chain().len(), 2);
+}
+
+#[test]
+fn longest_chain_through_a_cycle() {
+    // Enters the cycle at `a` and leaves it from `b`
+    let g = graph(&["x", "a", "b", "y"], &[(0, 1), (1, 2), (2, 1), (2, 3)]);
+    assert_eq!(chain_names(&g), vec!["x", "a", "y"]);
+}
+
+#[test]
+fn reachable_downstream_and_upstream() {
+    let g = graph(&["a", "b", "c", "d"], &[(0, 1), (1, 2), (3, 1)]);
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect();
+    assert_eq!(g.reachable([(1, 0)], false, None), keys(&[1, 2]));
+    assert_eq!(g.reachable([(1, 0)], true, None), keys(&[0, 1, 3]));
+    assert_eq!(
+        g.reachable([(0, 0), (3, 0)], false, None),
+        keys(&[0, 1, 2, 3])
+    );
+}
+
+#[test]
+fn reachable_within_depth() {
+    let g = graph(&["a", "b", "c", "d"], &[(0, 1), (1, 2), (2, 0), (2, 3)]);
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect();
+    assert_eq!(g.reachable([(0, 0)], false, Some(0)), keys(&[0]));
+    assert_eq!(g.reachable([(0, 0)], false, Some(2)), keys(&[0, 1, 2]));
+    // The cycle back to `a` is not followed forever
+    assert_eq!(g.reachable([(0, 0)], false, Some(10)), keys(&[0, 1, 2, 3]));
+}
+
+#[test]
+fn depths_from_nearest_root() {
+    // `d` only hangs off the cycle between `b` and `c`, which nothing enters
+    let g = graph(
+        &["a", "b", "c", "d", "e"],
+        &[(0, 4), (1, 2), (2, 1), (2, 3)],
+    );
+    let depths = g.depths();
+    assert_eq!(depths.get(&(0, 0)), Some(&0));
+    assert_eq!(depths.get(&(4, 0)), Some(&1));
+    assert_eq!(depths.get(&(1, 0)), None);
+    assert_eq!(depths.get(&(3, 0)), None);
+}
+
+#[test]
+fn self_triggering_across_instantiations() {
+    let g = graph(&["a", "a", "b", "b"], &[(0, 1), (1, 2), (3, 3)]);
+    assert_eq!(
+        g.self_triggering(),
+        vec![("a".to_string(), 1), ("b".to_string(), 1)]
+    );
+}
+
+#[test]
+fn topological_order_and_neighbours() {
+    let g = graph(&["a", "b", "c", "d"], &[(2, 1), (1, 0), (0, 1), (3, 2)]);
+    let order = g.topo_order();
+    let position = |i: u64| order.iter().position(|k| *k == (i, 0)).unwrap();
+    assert!(position(3) < position(2));
+    assert!(position(2) < position(1).min(position(0)));
+    let condensation = g.condensation();
+    assert_eq!(condensation.components.len(), 3);
+    assert_eq!(
+        condensation.component[&(0, 0)],
+        condensation.component[&(1, 0)]
+    );
+
+    let mut preds: Vec<_> = g.predecessors((1, 0)).collect();
+    preds.sort();
+    assert_eq!(preds, vec![(0, 0), (2, 0)]);
+    assert_eq!(g.reverse_edges()[&(1, 0)].len(), 2);
+    assert_eq!(g.successors((3, 0)).collect::<Vec<_>>(), vec![(2, 0)]);
+}
+
+#[test]
+fn betweenness_splits_between_shortest_paths() {
+    // Two equally short ways from `a` to `d`, then one on to `e`
+    let g = graph(
+        &["a", "b", "c", "d", "e"],
+        &[(0, 1), (0, 2), (1, 3), (2, 3), (3, 4)],
+    );
+    let betweenness = g.betweenness_centrality();
+    let scores: Vec<f64> = (0..5).map(|i| betweenness[&(i, 0)]).collect();
+    assert_eq!(scores, vec![0., 1., 1., 3., 0.]);
+    let degree = g.degree_centrality();
+    let degrees: Vec<f64> = (0..5).map(|i| degree[&(i, 0)]).collect();
+    assert_eq!(degrees, vec![2., 2., 2., 3., 1.]);
+}
+
+#[test]
+fn layers_by_depth() {
+    // `d` and `e` only hang off the cycle between them
+    let g = graph(
+        &["a", "b", "c", "d", "e"],
+        &[(0, 1), (0, 2), (3, 4), (4, 3)],
+    );
+    let layers = g.layers();
+    let cells: Vec<(usize, usize)> = (0..5).map(|i| layers[&(i, 0)]).collect();
+    assert_eq!(cells, vec![(0, 0), (0, 1), (1, 1), (0, 2), (1, 2)]);
+}
+
+#[test]
+fn sorted_output_order() {
+    let g = graph(&["a", "b", "c", "d"], &[(3, 0), (0, 2), (0, 1), (2, 1)]);
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect::<Vec<_>>();
+    assert_eq!(g.sorted_nodes(), keys(&[0, 1, 2, 3]));
+    let edges: Vec<(u64, u64)> = g.sorted_edges().iter().map(|(s, t)| (s.0, t.0)).collect();
+    assert_eq!(edges, vec![(0, 1), (0, 2), (2, 1), (3, 0)]);
+    assert_eq!(g.weakly_connected_components(), vec![keys(&[0, 1, 2, 3])]);
+}
+
+#[test]
+fn light_edges_and_the_nodes_they_left_are_dropped() {
+    let mut g = graph(&["a", "b", "c", "d", "e"], &[(0, 1), (1, 2), (2, 3)]);
+    g.weights.insert(((0, 0), (1, 0)), 5);
+    g.weights.insert(((1, 0), (2, 0)), 3);
+    let heavy = g.without_edges_below(3);
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect::<Vec<_>>();
+    // `d` only had the light edge from `c`; `e` never had an edge
+    assert_eq!(heavy.sorted_nodes(), keys(&[0, 1, 2, 4]));
+    let edges: Vec<(u64, u64)> = heavy
+        .sorted_edges()
+        .iter()
+        .map(|(s, t)| (s.0, t.0))
+        .collect();
+    assert_eq!(edges, vec![(0, 1), (1, 2)]);
+    assert_eq!(g.without_edges_below(1).edge_count(), 3);
+}
+
+#[test]
+fn heaviest_edges_keep_exactly_n() {
+    let build = || {
+        let mut g = graph(
+            &["a", "b", "c", "d", "e"],
+            &[(0, 1), (1, 2), (2, 3), (3, 4), (0, 4)],
+        );
+        g.weights.insert(((0, 0), (1, 0)), 5);
+        for i in 1..4 {
+            g.weights.insert(((i, 0), (i + 1, 0)), 3);
+        }
+        g
+    };
+    let pruned = build().heaviest_edges(3);
+    let edges: Vec<(u64, u64)> = pruned
+        .sorted_edges()
+        .iter()
+        .map(|(s, t)| (s.0, t.0))
+        .collect();
+    // The heaviest, then two of the three tied at 3, evenly spaced in key order
+    assert_eq!(edges, vec![(0, 1), (1, 2), (2, 3)]);
+    assert_eq!(pruned.nodes.len(), 5);
+    let mut weights: Vec<(u64, u64)> = pruned.weights.keys().map(|(s, t)| (s.0, t.0)).collect();
+    weights.sort();
+    assert_eq!(weights, edges);
+    for _ in 0..10 {
+        assert_eq!(
+            build().heaviest_edges(3).sorted_edges(),
+            pruned.sorted_edges()
+        );
+    }
+}
+
+#[test]
+fn transitive_reduction_keeps_cycles() {
+    // u -> v -> w makes u -> w redundant; the cycle of v and x stays whole
+    let mut g = graph(
+        &["u", "v", "w", "x"],
+        &[(0, 1), (1, 2), (0, 2), (1, 3), (3, 1)],
+    );
+    g.weights.insert(((0, 0), (2, 0)), 4);
+    g.weights.insert(((1, 0), (3, 0)), 2);
+    let reduced = g.transitive_reduction();
+    let edges: Vec<(u64, u64)> = reduced
+        .sorted_edges()
+        .iter()
+        .map(|(s, t)| (s.0, t.0))
+        .collect();
+    assert_eq!(edges, vec![(0, 1), (1, 2), (1, 3), (3, 1)]);
+    assert!(!reduced.weights.contains_key(&((0, 0), (2, 0))));
+    assert_eq!(reduced.weight((1, 0), (3, 0)), 2);
+}
+
+#[test]
+fn version_windows_merge_within_a_window() {
+    let mut g = InstantiationGraph::default();
+    for (key, name) in [
+        ((1, 0), "p"),
+        ((2, 1), "p"),
+        ((3, 3), "p"),
+        ((4, 0), "q"),
+        ((5, 2), "q"),
+    ] {
+        g.nodes.insert(key);
+        g.names.insert(key, name.to_string());
+    }
+    for (src, tgt, weight) in [
+        ((1, 0), (4, 0), 2),
+        ((2, 1), (4, 0), 3),
+        ((2, 1), (3, 3), 1),
+        ((3, 3), (5, 2), 1),
+    ] {
+        g.edges.entry(src).or_default().insert(tgt);
+        g.weights.insert((src, tgt), weight);
+    }
+    // Nodes are keyed by the name's position in sorted order and the window
+    let merged = g.version_windows(2);
+    assert_eq!(merged.sorted_nodes(), vec![(0, 0), (0, 1), (1, 0), (1, 1)]);
+    assert_eq!(merged.names[&(0, 1)], "p");
+    assert_eq!(
+        merged.sorted_edges(),
+        vec![((0, 0), (0, 1)), ((0, 0), (1, 0)), ((0, 1), (1, 1))]
+    );
+    // Both edges of versions 0 and 1 of `p` into version 0 of `q`
+    assert_eq!(merged.weight((0, 0), (1, 0)), 5);
+    assert_eq!(merged.weight((0, 1), (1, 1)), 1);
+}
+
+#[test]
+fn condensed_into_a_dag() {
+    let g = graph(
+        &["a", "b", "c", "d"],
+        &[(0, 1), (1, 0), (1, 2), (0, 2), (2, 2), (2, 3)],
+    );
+    let condensed = g.condensed();
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect::<Vec<_>>();
+    assert_eq!(condensed.sorted_nodes(), keys(&[0, 2, 3]));
+    assert_eq!(condensed.names[&(0, 0)], "a, b (cycle of 2)");
+    // A self-loop makes a cycle of one
+    assert_eq!(condensed.names[&(2, 0)], "c (cycle of 1)");
+    assert_eq!(condensed.names[&(3, 0)], "d");
+    let cycles: BTreeMap<u64, usize> = condensed.cycles.iter().map(|(k, n)| (k.0, *n)).collect();
+    assert_eq!(cycles, BTreeMap::from([(0, 2), (2, 1)]));
+    let edges: Vec<(u64, u64)> = condensed
+        .sorted_edges()
+        .iter()
+        .map(|(s, t)| (s.0, t.0))
+        .collect();
+    assert_eq!(edges, vec![(0, 2), (2, 3)]);
+    // Both edges from the cycle of `a` and `b` into `c`
+    assert_eq!(condensed.weight((0, 0), (2, 0)), 2);
+}
+
+#[test]
+fn loops_closing_through_discovered_are_ignored() {
+    // `a -> b -> c -> a` only closes through the discovered `b`
+    let mut g = graph(
+        &["a", "b", "c", "d", "e"],
+        &[(0, 1), (1, 2), (2, 0), (3, 4), (4, 3)],
+    );
+    g.discovered.insert((1, 0));
+    let loops = |ignore_discovered| {
+        let mut loops: Vec<Vec<u64>> = g
+            .matching_loops(ignore_discovered)
+            .into_iter()
+            .map(|scc| {
+                let mut ids: Vec<u64> = scc.iter().map(|k| k.0).collect();
+                ids.sort();
+                ids
+            })
+            .collect();
+        loops.sort();
+        loops
+    };
+    assert_eq!(loops(false), vec![vec![0, 1, 2], vec![3, 4]]);
+    assert_eq!(loops(true), vec![vec![3, 4]]);
+}
+
+#[test]
+fn largest_components_are_kept() {
+    // Components of three, two and one nodes
+    let g = graph(&["a", "b", "c", "d", "e", "f"], &[(0, 1), (1, 2), (3, 4)]);
+    let keys = |ids: &[u64]| ids.iter().map(|i| (*i, 0)).collect();
+
+    let (kept, total) = g.largest_components(1);
+    assert_eq!(total, 3);
+    assert_eq!(kept.nodes, keys(&[0, 1, 2]));
+    assert_eq!(g.nodes.len() - kept.nodes.len(), 3);
+    assert!(!kept.edges.contains_key(&(3, 0)));
+
+    let (kept, total) = g.largest_components(2);
+    assert_eq!(total.saturating_sub(2), 1);
+    assert_eq!(kept.nodes, keys(&[0, 1, 2, 3, 4]));
+
+    let (kept, _) = g.largest_components(5);
+    assert_eq!(kept.nodes, g.nodes);
+}
+
+#[test]
+fn components_do_not_depend_on_hash_order() {
+    // Every map has its own hash seed, so equal graphs iterate differently
+    let edges = [
+        (0, 1),
+        (1, 0),
+        (2, 3),
+        (3, 2),
+        (4, 5),
+        (5, 4),
+        (1, 2),
+        (3, 4),
+    ];
+    let names = ["a", "b", "c", "d", "e", "f"];
+    let first = graph(&names, &edges).condensation().components;
+    for _ in 0..10 {
+        assert_eq!(graph(&names, &edges).condensation().components, first);
+    }
+}
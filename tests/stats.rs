@@ -0,0 +1,100 @@
+//! Lines of the text reports.
+
+use std::time::Duration;
+
+use gordian_profiler::{default_cost, stat_line, Demangle, InstantiationGraph, Timings};
+use z3tracer::model::QuantCost;
+
+fn cost(quant: &str, instantiations: u64) -> QuantCost {
+    QuantCost {
+        quant: quant.to_string(),
+        instantiations,
+        cost: instantiations,
+    }
+}
+
+#[test]
+fn sub_percent_share_is_not_truncated() {
+    assert_eq!(
+        stat_line(&cost("rare", 8), 1000),
+        "Instantiated rare 8 times (0.8% of the total), cost 8, instantiations × cost 64"
+    );
+}
+
+#[test]
+fn large_counts_do_not_overflow() {
+    assert_eq!(
+        stat_line(&cost("hot", u64::MAX / 2), u64::MAX),
+        format!(
+            "Instantiated hot {0} times (50.0% of the total), cost {0}, instantiations × cost {1}",
+            u64::MAX / 2,
+            (u64::MAX / 2) as u128 * (u64::MAX / 2) as u128
+        )
+    );
+}
+
+#[test]
+fn large_costs_do_not_overflow() {
+    let score = default_cost(&cost("hot", u64::MAX / 2), &InstantiationGraph::default());
+    let exact = (u64::MAX / 2) as f64 * (u64::MAX / 2) as f64;
+    assert_eq!(score, exact);
+    assert!(score > u64::MAX as f64);
+}
+
+#[test]
+fn demangled_names() {
+    let cases = [
+        (
+            "@MaxFuel_assumption",
+            "@MaxFuel_assumption",
+            "MaxFuel_assumption",
+            "@MaxFuel_assumption",
+        ),
+        (
+            "Tm_refine_0b0d7a1c5e8f",
+            "Tm_refine_0b0d7a1c5e8f",
+            "Tm_refine",
+            "Tm_refine",
+        ),
+        (
+            "_module.__default.Foo_0123abcd",
+            "_module.__default.Foo_0123abcd",
+            "_module.__default.Foo",
+            "Foo",
+        ),
+        ("_module.Bar@3", "_module.Bar@3", "_module.Bar", "Bar"),
+        ("lemma.deadbeef@12", "lemma.deadbeef@12", "lemma", "lemma"),
+        ("short_abc", "short_abc", "short_abc", "short_abc"),
+    ];
+    for (raw, none, fstar, dafny) in cases {
+        assert_eq!(Demangle::None.apply(raw), none);
+        assert_eq!(Demangle::Fstar.apply(raw), fstar, "{}", raw);
+        assert_eq!(Demangle::Dafny.apply(raw), dafny, "{}", raw);
+    }
+    assert_eq!(Demangle::StripHash.apply("lemma.deadbeef@12"), "lemma");
+    assert_eq!(
+        Demangle::StripHash.apply("@MaxFuel_assumption"),
+        "@MaxFuel_assumption"
+    );
+    assert_eq!(Demangle::StripHash.apply("_module.Bar@3"), "_module.Bar");
+    // A bare `@N` is a name, not a suffix
+    assert_eq!(Demangle::StripHash.apply("@1"), "@1");
+}
+
+#[test]
+fn timings_show_each_phase_share() {
+    let timings = Timings {
+        line_count: Duration::from_millis(250),
+        parse: Duration::from_millis(500),
+        graph: Duration::from_millis(250),
+        stats: Duration::ZERO,
+    };
+    let report = timings.render();
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 5);
+    assert_eq!(lines[0], "Line count:       0.250s  25.0%");
+    assert_eq!(lines[1], "Parse:            0.500s  50.0%");
+    assert_eq!(lines[4], "Total:            1.000s");
+    // Nothing measured, e.g. for a loaded profile
+    assert!(Timings::default().render().contains("0.0%"));
+}